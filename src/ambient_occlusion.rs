@@ -0,0 +1,84 @@
+use crate::rgb_image::RGBImage;
+use crate::shader::DepthBuffer;
+use std::f32::consts::{FRAC_PI_2, PI};
+
+// Screen space ambient occlusion, max elevation angle method
+// https://github.com/ssloy/tinyrenderer/wiki/Lesson-8:-Ambient-occlusion
+
+const DIRECTIONS: usize = 8;
+
+impl DepthBuffer {
+    // Steepest slope of the depth buffer seen from the pixel in the given direction
+    fn max_elevation_angle(&self, x: i32, y: i32, direction: (f32, f32), radius: u16) -> f32 {
+        let z = self.get(x, y).unwrap_or(-1.0);
+        let mut max_angle: f32 = 0.0;
+        for distance in 1..=radius {
+            let distance = distance as f32;
+            let sx = x + (direction.0 * distance).round() as i32;
+            let sy = y + (direction.1 * distance).round() as i32;
+            let sz = match self.get(sx, sy) {
+                Some(sz) => sz,
+                None => break,
+            };
+            // greater depth is closer to the viewer, so it covers the pixel
+            max_angle = max_angle.max(((sz - z) / distance).atan());
+        }
+        max_angle
+    }
+
+    /// Fraction of the hemisphere above every pixel which is not covered by
+    /// neighbour pixels within `radius`, 1 is fully open.
+    pub fn ambient_occlusion(&self, radius: u16) -> Vec<f32> {
+        let directions: Vec<(f32, f32)> = (0..DIRECTIONS)
+            .map(|i| {
+                let angle = 2.0 * PI * i as f32 / DIRECTIONS as f32;
+                (angle.cos(), angle.sin())
+            })
+            .collect();
+        let mut occlusion = Vec::with_capacity(self.depth.len());
+        for y in 0..self.height as i32 {
+            for x in 0..self.width as i32 {
+                if self.get(x, y).unwrap_or(-1.0) < 0.0 {
+                    occlusion.push(1.0);
+                    continue;
+                }
+                let open: f32 = directions
+                    .iter()
+                    .map(|&direction| FRAC_PI_2 - self.max_elevation_angle(x, y, direction, radius))
+                    .sum();
+                occlusion.push(open / (FRAC_PI_2 * DIRECTIONS as f32));
+            }
+        }
+        occlusion
+    }
+}
+
+impl RGBImage {
+    /// Darkens pixels by ambient occlusion estimated from the `depth` buffer of the same size.
+    /// `strength` 0 keeps the image as is, 1 makes fully occluded pixels black.
+    pub fn apply_ambient_occlusion(&mut self, depth: &DepthBuffer, radius: u16, strength: f32) {
+        assert_eq!((depth.width, depth.height), (self.width, self.height));
+        let occlusion = depth.ambient_occlusion(radius);
+        for (pixel, open) in self.pixels.iter_mut().zip(occlusion) {
+            let intensity = 1.0 - strength * (1.0 - open);
+            *pixel = pixel.with_intensity(intensity.max(0.0));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ambient_occlusion() {
+        // flat floor with a wall on the right side
+        let mut depth = DepthBuffer::new(8, 1);
+        depth.depth = vec![1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 100.0];
+        let occlusion = depth.ambient_occlusion(4);
+        assert!(occlusion[0] > 0.999);
+        assert!(occlusion[6] < occlusion[4]);
+        assert!(occlusion[4] < 1.0);
+        assert!(occlusion[7] > 0.999);
+    }
+}
@@ -0,0 +1,242 @@
+use crate::matrix::Matrix;
+use crate::point::{cross, diff, dot_product, Vec3};
+use crate::wireframe::WireframeModel;
+
+// Same matrices as `gluLookAt`, `gluPerspective` and `glOrtho`
+// https://www.khronos.org/opengl/wiki/GluPerspective_code
+// https://www.khronos.org/registry/OpenGL-Refpages/gl2.1/xhtml/glOrtho.xml
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Projection {
+    /// Vertical field of view `fov` in radians, the horizontal one follows `aspect`
+    Perspective { fov: f32, aspect: f32 },
+    /// Parallel projection of the box between `left`, `right`, `bottom` and `top`
+    /// in camera coordinates
+    Orthographic {
+        left: f32,
+        right: f32,
+        bottom: f32,
+        top: f32,
+    },
+}
+
+/// Camera looking from `position` to `target`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Camera {
+    pub position: Vec3<f32>,
+    pub target: Vec3<f32>,
+    pub up: Vec3<f32>,
+    pub projection: Projection,
+    /// Distance to the near clipping plane
+    pub near: f32,
+    /// Distance to the far clipping plane
+    pub far: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera {
+            position: Vec3 {
+                x: 1.0,
+                y: 1.0,
+                z: 3.0,
+            },
+            target: Vec3 {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            up: Vec3 {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0,
+            },
+            projection: Projection::Perspective {
+                fov: 40_f32.to_radians(),
+                aspect: 1.0,
+            },
+            near: 1.0,
+            far: 10.0,
+        }
+    }
+}
+
+impl Camera {
+    /// World to camera coordinates, the camera looks along `-z`.
+    pub(crate) fn view(&self) -> Matrix {
+        let z = diff(self.position, self.target).normalized();
+        let x = cross(self.up, z).normalized();
+        let y = cross(z, x);
+        let mut m = Matrix::new_identity(4);
+        for (i, axis) in [x, y, z].iter().enumerate() {
+            m.m[i][0] = axis.x;
+            m.m[i][1] = axis.y;
+            m.m[i][2] = axis.z;
+            m.m[i][3] = -dot_product(*axis, self.position);
+        }
+        m
+    }
+
+    /// Camera to clip coordinates. Depth is reversed comparing to OpenGL:
+    /// the near plane goes to 1 and the far one to -1, so greater is closer as in z-buffers.
+    pub(crate) fn projection(&self) -> Matrix {
+        let (near, far) = (self.near, self.far);
+        let mut m = Matrix::new(4, 4);
+        match self.projection {
+            Projection::Perspective { fov, aspect } => {
+                let f = 1.0 / (fov / 2.0).tan();
+                m.m[0][0] = f / aspect;
+                m.m[1][1] = f;
+                m.m[2][2] = (far + near) / (far - near);
+                m.m[2][3] = 2.0 * far * near / (far - near);
+                m.m[3][2] = -1.0;
+            }
+            Projection::Orthographic {
+                left,
+                right,
+                bottom,
+                top,
+            } => {
+                m.m[0][0] = 2.0 / (right - left);
+                m.m[0][3] = -(right + left) / (right - left);
+                m.m[1][1] = 2.0 / (top - bottom);
+                m.m[1][3] = -(top + bottom) / (top - bottom);
+                m.m[2][2] = 2.0 / (far - near);
+                m.m[2][3] = (far + near) / (far - near);
+                m.m[3][3] = 1.0;
+            }
+        }
+        m
+    }
+
+    /// World to clip coordinates.
+    pub(crate) fn view_projection(&self) -> Matrix {
+        self.projection() * self.view()
+    }
+
+    /// Faces sorted by the distance of their centers from the camera plane, the farthest first,
+    /// the order of drawing transparent faces.
+    pub fn back_to_front(
+        &self,
+        model: &WireframeModel,
+        faces: impl IntoIterator<Item = usize>,
+    ) -> Vec<usize> {
+        let view = self.view();
+        // the camera looks along `-z`, so farther faces have smaller `z`
+        let mut depths: Vec<(usize, f32)> = faces
+            .into_iter()
+            .map(|face| {
+                let p = model.faces[face].map(|f| model.vertexes[f.vertex_index]);
+                let center = Vec3 {
+                    x: (p[0].x + p[1].x + p[2].x) / 3.0,
+                    y: (p[0].y + p[1].y + p[2].y) / 3.0,
+                    z: (p[0].z + p[1].z + p[2].z) / 3.0,
+                };
+                (face, view.transform(center).z)
+            })
+            .collect();
+        depths.sort_by(|a, b| a.1.total_cmp(&b.1));
+        depths.into_iter().map(|(face, _)| face).collect()
+    }
+
+    /// Unit direction from the point `p` to the viewer, the same for all points
+    /// with the orthographic projection.
+    pub(crate) fn view_direction(&self, p: Vec3<f32>) -> Vec3<f32> {
+        match self.projection {
+            Projection::Perspective { .. } => diff(self.position, p).normalized(),
+            Projection::Orthographic { .. } => diff(self.position, self.target).normalized(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_camera_matrices() {
+        let camera = Camera {
+            position: Vec3 {
+                x: 0.0,
+                y: 0.0,
+                z: 5.0,
+            },
+            near: 1.0,
+            far: 10.0,
+            ..Camera::default()
+        };
+        let z = |distance: f32| {
+            let v = Vec3 {
+                x: 0.0,
+                y: 0.0,
+                z: 5.0 - distance,
+            };
+            (camera.projection() * camera.view()).transform(v).z
+        };
+        assert!((z(1.0) - 1.0).abs() < 1e-6);
+        assert!((z(10.0) + 1.0).abs() < 1e-6);
+        assert!(z(2.0) > z(3.0));
+
+        let edge = Vec3 {
+            x: 0.0,
+            y: 20_f32.to_radians().tan() * 5.0,
+            z: 0.0,
+        };
+        let y = (camera.projection() * camera.view()).transform(edge).y;
+        assert!((y - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_orthographic() {
+        let camera = Camera {
+            position: Vec3 {
+                x: 0.0,
+                y: 0.0,
+                z: 5.0,
+            },
+            projection: Projection::Orthographic {
+                left: -2.0,
+                right: 2.0,
+                bottom: -1.0,
+                top: 1.0,
+            },
+            near: 1.0,
+            far: 10.0,
+            ..Camera::default()
+        };
+        let m = camera.projection() * camera.view();
+        let p = |x: f32, y: f32, distance: f32| {
+            m.transform(Vec3 {
+                x,
+                y,
+                z: 5.0 - distance,
+            })
+        };
+        // sizes don't depend on the distance
+        assert_eq!(p(2.0, 1.0, 1.0).x, 1.0);
+        assert_eq!(p(2.0, 1.0, 9.0).y, 1.0);
+        assert_eq!(p(-2.0, 0.0, 9.0).x, -1.0);
+        assert!((p(0.0, 0.0, 1.0).z - 1.0).abs() < 1e-6);
+        assert!((p(0.0, 0.0, 10.0).z + 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_back_to_front() {
+        let face = |i: usize| {
+            [i, i, i].map(|vertex_index| crate::wireframe::Face {
+                vertex_index,
+                texture_index: None,
+                norm_index: None,
+            })
+        };
+        let z = |z: f32| Vec3 { x: 0.0, y: 0.0, z };
+        let model = WireframeModel {
+            vertexes: vec![z(0.0), z(1.0), z(-1.0)],
+            faces: vec![face(0), face(1), face(2)],
+            ..WireframeModel::default()
+        };
+        let camera = Camera::default();
+        assert_eq!(camera.back_to_front(&model, 0..3), vec![2, 0, 1]);
+        assert_eq!(camera.back_to_front(&model, [1, 0]), vec![0, 1]);
+    }
+}
@@ -0,0 +1,129 @@
+use crate::point::{Vec3, Vec4};
+
+// Sutherland–Hodgman clipping against the view frustum in homogeneous clip space
+// https://en.wikipedia.org/wiki/Sutherland%E2%80%93Hodgman_algorithm
+
+// Points with smaller `w` are behind the camera or on its plane and can't be divided by `w`
+const MIN_W: f32 = 1e-5;
+
+/// Vertex of a clipped polygon with barycentric coordinates `bc` in the original triangle.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) struct ClipVertex {
+    pub(crate) position: Vec4<f32>,
+    pub(crate) bc: Vec3<f32>,
+}
+
+const PLANES: usize = 7;
+
+// Signed distances to the frustum planes, the point is inside when all of them are not negative
+fn distances(p: Vec4<f32>) -> [f32; PLANES] {
+    [
+        p.w + p.x,
+        p.w - p.x,
+        p.w + p.y,
+        p.w - p.y,
+        p.w + p.z,
+        p.w - p.z,
+        p.w - MIN_W,
+    ]
+}
+
+fn lerp(a: &ClipVertex, b: &ClipVertex, t: f32) -> ClipVertex {
+    let mix = |a: f32, b: f32| a + (b - a) * t;
+    ClipVertex {
+        position: Vec4 {
+            x: mix(a.position.x, b.position.x),
+            y: mix(a.position.y, b.position.y),
+            z: mix(a.position.z, b.position.z),
+            w: mix(a.position.w, b.position.w),
+        },
+        bc: Vec3 {
+            x: mix(a.bc.x, b.bc.x),
+            y: mix(a.bc.y, b.bc.y),
+            z: mix(a.bc.z, b.bc.z),
+        },
+    }
+}
+
+/// Part of the triangle inside of `-w <= x, y, z <= w` as a convex polygon,
+/// empty if the triangle is not visible at all.
+pub(crate) fn clip_triangle(points: [Vec4<f32>; 3]) -> Vec<ClipVertex> {
+    let corner = |i: usize, x: f32, y: f32, z: f32| ClipVertex {
+        position: points[i],
+        bc: Vec3 { x, y, z },
+    };
+    let mut polygon = vec![
+        corner(0, 1.0, 0.0, 0.0),
+        corner(1, 0.0, 1.0, 0.0),
+        corner(2, 0.0, 0.0, 1.0),
+    ];
+    for plane in 0..PLANES {
+        let distance = |v: &ClipVertex| distances(v.position)[plane];
+        if polygon.iter().all(|v| distance(v) >= 0.0) {
+            continue;
+        }
+        let mut clipped = Vec::with_capacity(polygon.len() + 1);
+        for (i, current) in polygon.iter().enumerate() {
+            let next = &polygon[(i + 1) % polygon.len()];
+            let (d0, d1) = (distance(current), distance(next));
+            if d0 >= 0.0 {
+                clipped.push(*current);
+            }
+            if (d0 >= 0.0) != (d1 >= 0.0) {
+                clipped.push(lerp(current, next, d0 / (d0 - d1)));
+            }
+        }
+        if clipped.len() < 3 {
+            return vec![];
+        }
+        polygon = clipped;
+    }
+    polygon
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f32, y: f32, z: f32, w: f32) -> Vec4<f32> {
+        Vec4 { x, y, z, w }
+    }
+
+    #[test]
+    fn test_inside_triangle() {
+        let points = [
+            point(0.0, 0.0, 0.0, 1.0),
+            point(0.5, 0.0, 0.0, 1.0),
+            point(0.0, 0.5, 0.0, 1.0),
+        ];
+        let polygon = clip_triangle(points);
+        assert_eq!(polygon.len(), 3);
+        assert_eq!(polygon[1].position, points[1]);
+        assert_eq!(polygon[1].bc.y, 1.0);
+    }
+
+    #[test]
+    fn test_clip_triangle() {
+        // the second vertex is to the right of the frustum
+        let polygon = clip_triangle([
+            point(0.0, 0.0, 0.0, 1.0),
+            point(3.0, 0.0, 0.0, 1.0),
+            point(0.0, 0.5, 0.0, 1.0),
+        ]);
+        assert_eq!(polygon.len(), 4);
+        assert!(polygon.iter().all(|v| v.position.x <= v.position.w));
+        assert_eq!(polygon[1].position.x, 1.0);
+        assert!((polygon[1].bc.y - 1.0 / 3.0).abs() < 1e-6);
+        for v in &polygon {
+            assert!((v.bc.x + v.bc.y + v.bc.z - 1.0).abs() < 1e-6);
+        }
+
+        // behind the camera
+        let polygon = clip_triangle([
+            point(0.0, 0.0, 0.0, -1.0),
+            point(0.1, 0.0, 0.0, -1.0),
+            point(0.0, 0.1, 0.0, -2.0),
+        ]);
+        assert!(polygon.is_empty());
+    }
+}
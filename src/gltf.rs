@@ -0,0 +1,847 @@
+use crate::json::Json;
+use crate::material::Material;
+use crate::point::Vec3;
+use crate::texture::Texture;
+use crate::wireframe::{relative_path, Face, Group, WireframeModel};
+use image::DynamicImage;
+use std::fmt::{Display, Formatter};
+use std::fs;
+
+// glTF 2.0 importer for local .gltf and .glb files
+// https://registry.khronos.org/glTF/specs/2.0/glTF-2.0.html
+
+#[derive(Debug)]
+pub enum GltfError {
+    Io {
+        path: String,
+        error: std::io::Error,
+    },
+    Image {
+        path: String,
+        error: image::ImageError,
+    },
+    Format {
+        path: String,
+        message: String,
+    },
+}
+
+impl Display for GltfError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GltfError::Io { path, error } => write!(f, "{}: {}", path, error),
+            GltfError::Image { path, error } => write!(f, "{}: {}", path, error),
+            GltfError::Format { path, message } => write!(f, "{}: {}", path, message),
+        }
+    }
+}
+
+impl std::error::Error for GltfError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GltfError::Io { error, .. } => Some(error),
+            GltfError::Image { error, .. } => Some(error),
+            GltfError::Format { .. } => None,
+        }
+    }
+}
+
+// Column-major 4x4 matrix as stored in gltf files
+type Mat4 = [f32; 16];
+
+const IDENTITY: Mat4 = [
+    1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+];
+
+fn mul(a: &Mat4, b: &Mat4) -> Mat4 {
+    let mut m = [0.0; 16];
+    for c in 0..4 {
+        for r in 0..4 {
+            m[c * 4 + r] = (0..4).map(|k| a[k * 4 + r] * b[c * 4 + k]).sum();
+        }
+    }
+    m
+}
+
+fn transform_point(m: &Mat4, p: Vec3<f32>) -> Vec3<f32> {
+    Vec3 {
+        x: m[0] * p.x + m[4] * p.y + m[8] * p.z + m[12],
+        y: m[1] * p.x + m[5] * p.y + m[9] * p.z + m[13],
+        z: m[2] * p.x + m[6] * p.y + m[10] * p.z + m[14],
+    }
+}
+
+// Normals are transformed with the cofactor matrix, i.e. inverse transpose scaled by determinant
+fn transform_normal(m: &Mat4, n: Vec3<f32>) -> Vec3<f32> {
+    let a = |r: usize, c: usize| m[c * 4 + r];
+    let cofactor = |r: usize, c: usize| {
+        let (r0, r1) = ((r + 1) % 3, (r + 2) % 3);
+        let (c0, c1) = ((c + 1) % 3, (c + 2) % 3);
+        a(r0, c0) * a(r1, c1) - a(r0, c1) * a(r1, c0)
+    };
+    let sign = determinant(m).signum();
+    let v = [n.x, n.y, n.z];
+    let row = |r: usize| sign * (0..3).map(|c| cofactor(r, c) * v[c]).sum::<f32>();
+    Vec3 {
+        x: row(0),
+        y: row(1),
+        z: row(2),
+    }
+    .normalized()
+}
+
+fn determinant(m: &Mat4) -> f32 {
+    m[0] * (m[5] * m[10] - m[9] * m[6]) - m[4] * (m[1] * m[10] - m[9] * m[2])
+        + m[8] * (m[1] * m[6] - m[5] * m[2])
+}
+
+// translation * rotation * scale
+fn trs(t: [f32; 3], q: [f32; 4], s: [f32; 3]) -> Mat4 {
+    let [x, y, z, w] = q;
+    [
+        (1.0 - 2.0 * (y * y + z * z)) * s[0],
+        (2.0 * (x * y + z * w)) * s[0],
+        (2.0 * (x * z - y * w)) * s[0],
+        0.0,
+        (2.0 * (x * y - z * w)) * s[1],
+        (1.0 - 2.0 * (x * x + z * z)) * s[1],
+        (2.0 * (y * z + x * w)) * s[1],
+        0.0,
+        (2.0 * (x * z + y * w)) * s[2],
+        (2.0 * (y * z - x * w)) * s[2],
+        (1.0 - 2.0 * (x * x + y * y)) * s[2],
+        0.0,
+        t[0],
+        t[1],
+        t[2],
+        1.0,
+    ]
+}
+
+pub(crate) fn base64_decode(s: &str) -> Option<Vec<u8>> {
+    let value = |c: u8| match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' | b'-' => Some(62),
+        b'/' | b'_' => Some(63),
+        _ => None,
+    };
+    let mut bytes = Vec::with_capacity(s.len() * 3 / 4);
+    let mut bits: u32 = 0;
+    let mut count = 0;
+    for c in s.bytes().filter(|&c| c != b'=' && !c.is_ascii_whitespace()) {
+        bits = bits << 6 | value(c)? as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            bytes.push((bits >> count) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = s
+            .get(i + 1..i + 3)
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+struct Document<'a> {
+    path: &'a str,
+    json: Json,
+    buffers: Vec<Vec<u8>>,
+}
+
+const EMPTY: &[Json] = &[];
+
+impl<'a> Document<'a> {
+    fn error(&self, message: String) -> GltfError {
+        GltfError::Format {
+            path: self.path.to_string(),
+            message,
+        }
+    }
+
+    fn items(&self, key: &str) -> &[Json] {
+        self.json
+            .get(key)
+            .and_then(|a| a.as_array())
+            .unwrap_or(EMPTY)
+    }
+
+    fn item(&self, key: &str, index: usize) -> Result<&Json, GltfError> {
+        self.items(key)
+            .get(index)
+            .ok_or_else(|| self.error(format!("{} {} not found", key, index)))
+    }
+
+    fn index(&self, json: &Json, key: &str) -> Result<usize, GltfError> {
+        json.get(key)
+            .and_then(|i| i.as_usize())
+            .ok_or_else(|| self.error(format!("expected `{}` index", key)))
+    }
+
+    fn indices(json: Option<&Json>) -> Vec<usize> {
+        let items = json.and_then(|a| a.as_array()).unwrap_or(EMPTY);
+        items.iter().filter_map(|i| i.as_usize()).collect()
+    }
+
+    fn floats<const N: usize>(json: &Json, key: &str, default: [f32; N]) -> [f32; N] {
+        let values = json.get(key).and_then(|v| v.as_array()).unwrap_or(EMPTY);
+        let mut result = default;
+        if values.len() == N {
+            for (r, v) in result.iter_mut().zip(values) {
+                *r = v.as_f64().unwrap_or_default() as f32;
+            }
+        }
+        result
+    }
+
+    // Local files and data uris, nothing is downloaded
+    fn read_uri(&self, uri: &str) -> Result<Vec<u8>, GltfError> {
+        if let Some(data) = uri.strip_prefix("data:") {
+            let (_, encoded) = data
+                .split_once(";base64,")
+                .ok_or_else(|| self.error("only base64 data uris are supported".to_string()))?;
+            return base64_decode(encoded)
+                .ok_or_else(|| self.error("invalid base64 data uri".to_string()));
+        }
+        if uri.contains("://") {
+            return Err(self.error(format!("only local files are supported, found `{}`", uri)));
+        }
+        let path = relative_path(self.path, &percent_decode(uri));
+        fs::read(&path).map_err(|error| GltfError::Io { path, error })
+    }
+
+    fn load_buffers(&mut self, bin: Option<Vec<u8>>) -> Result<(), GltfError> {
+        let mut bin = bin;
+        let mut buffers = vec![];
+        for buffer in self.items("buffers") {
+            let data = match buffer.get("uri").and_then(|u| u.as_str()) {
+                Some(uri) => self.read_uri(uri)?,
+                None => bin
+                    .take()
+                    .ok_or_else(|| self.error("buffer without uri or binary chunk".to_string()))?,
+            };
+            let length = buffer.get("byteLength").and_then(|l| l.as_usize());
+            if length.is_none_or(|length| data.len() < length) {
+                return Err(self.error("buffer is shorter than its byteLength".to_string()));
+            }
+            buffers.push(data);
+        }
+        self.buffers = buffers;
+        Ok(())
+    }
+
+    fn buffer_view(&self, index: usize) -> Result<(&[u8], Option<usize>), GltfError> {
+        let view = self.item("bufferViews", index)?;
+        let buffer = self
+            .buffers
+            .get(self.index(view, "buffer")?)
+            .ok_or_else(|| {
+                self.error(format!("bufferView {} refers to a missing buffer", index))
+            })?;
+        let offset = view
+            .get("byteOffset")
+            .and_then(|o| o.as_usize())
+            .unwrap_or(0);
+        let length = self.index(view, "byteLength")?;
+        let data = offset
+            .checked_add(length)
+            .and_then(|end| buffer.get(offset..end))
+            .ok_or_else(|| self.error(format!("bufferView {} is out of bounds", index)))?;
+        let stride = view.get("byteStride").and_then(|s| s.as_usize());
+        Ok((data, stride))
+    }
+
+    /// Accessor values converted to `f64`, returns values and number of components per element.
+    fn accessor(&self, index: usize) -> Result<(Vec<f64>, usize), GltfError> {
+        let accessor = self.item("accessors", index)?;
+        let count = self.index(accessor, "count")?;
+        let components = match accessor.get("type").and_then(|t| t.as_str()) {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") => 4,
+            t => return Err(self.error(format!("unsupported accessor type {:?}", t))),
+        };
+        if accessor.get("sparse").is_some() {
+            return Err(self.error(format!("sparse accessor {} is not supported", index)));
+        }
+        let view = match accessor.get("bufferView") {
+            Some(_) => self.index(accessor, "bufferView")?,
+            None => {
+                // zeros stand in for data of other accessors, which takes a byte per element
+                // at least, so a larger count can only come from a broken file
+                let bytes: usize = self.buffers.iter().map(|b| b.len()).sum();
+                if count > bytes {
+                    return Err(self.error(format!("accessor {} is too large", index)));
+                }
+                return Ok((vec![0.0; count * components], components));
+            }
+        };
+        let component_type = self.index(accessor, "componentType")?;
+        let size = match component_type {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            t => return Err(self.error(format!("unsupported component type {}", t))),
+        };
+        let normalized = accessor.get("normalized") == Some(&Json::Bool(true));
+        let (data, stride) = self.buffer_view(view)?;
+        let offset = accessor
+            .get("byteOffset")
+            .and_then(|o| o.as_usize())
+            .unwrap_or(0);
+        let stride = stride.unwrap_or(size * components);
+        if stride < size * components {
+            return Err(self.error(format!(
+                "bufferView of accessor {} has invalid byteStride",
+                index
+            )));
+        }
+        // end of the last element, `None` on overflow
+        let end = count.checked_sub(1).map(|last| {
+            last.checked_mul(stride)
+                .and_then(|start| start.checked_add(offset))
+                .and_then(|start| start.checked_add(size * components))
+        });
+        if let Some(end) = end {
+            if end.is_none_or(|end| end > data.len()) {
+                return Err(self.error(format!("accessor {} is out of bounds", index)));
+            }
+        }
+
+        let mut values = Vec::with_capacity(count * components);
+        for i in 0..count {
+            for c in 0..components {
+                let start = offset + i * stride + c * size;
+                let b = &data[start..start + size];
+                let value = match component_type {
+                    5120 => Self::normalize(b[0] as i8 as f64, 127.0, normalized),
+                    5121 => Self::normalize(b[0] as f64, 255.0, normalized),
+                    5122 => Self::normalize(
+                        i16::from_le_bytes([b[0], b[1]]) as f64,
+                        32767.0,
+                        normalized,
+                    ),
+                    5123 => Self::normalize(
+                        u16::from_le_bytes([b[0], b[1]]) as f64,
+                        65535.0,
+                        normalized,
+                    ),
+                    5125 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    _ => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                };
+                values.push(value);
+            }
+        }
+        Ok((values, components))
+    }
+
+    fn normalize(value: f64, max: f64, normalized: bool) -> f64 {
+        if normalized {
+            (value / max).max(-1.0)
+        } else {
+            value
+        }
+    }
+
+    fn image(&self, index: usize) -> Result<DynamicImage, GltfError> {
+        let image = self.item("images", index)?;
+        let data = match image.get("uri").and_then(|u| u.as_str()) {
+            Some(uri) => self.read_uri(uri)?,
+            None => self
+                .buffer_view(self.index(image, "bufferView")?)?
+                .0
+                .to_vec(),
+        };
+        // texture coordinates are flipped as well, see `add_primitive`
+        image::load_from_memory(&data)
+            .map(|image| image.flipv())
+            .map_err(|error| GltfError::Image {
+                path: format!("{}#images[{}]", self.path, index),
+                error,
+            })
+    }
+
+    fn texture(&self, texture_info: Option<&Json>) -> Result<Option<Texture>, GltfError> {
+        let texture_info = match texture_info {
+            Some(info) => info,
+            None => return Ok(None),
+        };
+        let texture = self.item("textures", self.index(texture_info, "index")?)?;
+        match texture.get("source") {
+            Some(_) => Ok(Some(Texture::from(
+                self.image(self.index(texture, "source")?)?,
+            ))),
+            None => Ok(None),
+        }
+    }
+
+    fn materials(&self) -> Result<Vec<Material>, GltfError> {
+        let mut materials = vec![];
+        for (i, json) in self.items("materials").iter().enumerate() {
+            let name = json.get("name").and_then(|n| n.as_str());
+            let pbr = json.get("pbrMetallicRoughness").unwrap_or(&Json::Null);
+            let [r, g, b, a] = Self::floats(pbr, "baseColorFactor", [1.0; 4]);
+            materials.push(Material {
+                name: name.map_or_else(|| format!("material{}", i), |n| n.to_string()),
+                diffuse: Vec3 { x: r, y: g, z: b },
+                dissolve: a,
+                diffuse_map: self.texture(pbr.get("baseColorTexture"))?,
+                bump_map: self.texture(json.get("normalTexture"))?,
+                ..Material::default()
+            });
+        }
+        Ok(materials)
+    }
+
+    fn local_matrix(node: &Json) -> Mat4 {
+        if node.get("matrix").is_some() {
+            return Self::floats(node, "matrix", IDENTITY);
+        }
+        trs(
+            Self::floats(node, "translation", [0.0; 3]),
+            Self::floats(node, "rotation", [0.0, 0.0, 0.0, 1.0]),
+            Self::floats(node, "scale", [1.0; 3]),
+        )
+    }
+
+    /// Mesh instances in the scene with their names and world matrices.
+    fn meshes(&self) -> Result<Vec<(usize, String, Mat4)>, GltfError> {
+        let nodes = self.items("nodes");
+        if nodes.is_empty() {
+            return Ok((0..self.items("meshes").len())
+                .map(|i| (i, format!("mesh{}", i), IDENTITY))
+                .collect());
+        }
+        let roots: Vec<usize> = match self.items("scenes").first() {
+            Some(_) => {
+                let scene = self
+                    .json
+                    .get("scene")
+                    .and_then(|s| s.as_usize())
+                    .unwrap_or(0);
+                Self::indices(self.item("scenes", scene)?.get("nodes"))
+            }
+            None => {
+                let children: Vec<usize> = nodes
+                    .iter()
+                    .flat_map(|n| Self::indices(n.get("children")))
+                    .collect();
+                (0..nodes.len()).filter(|i| !children.contains(i)).collect()
+            }
+        };
+
+        let mut meshes = vec![];
+        // depth first traversal, `path` holds ancestors of the current node
+        let mut path: Vec<usize> = vec![];
+        let mut stack: Vec<(usize, usize, Mat4)> =
+            roots.iter().rev().map(|&i| (i, 0, IDENTITY)).collect();
+        while let Some((i, depth, parent)) = stack.pop() {
+            path.truncate(depth);
+            // malformed files may have cycles
+            if path.contains(&i) {
+                return Err(self.error(format!("node {} is its own ancestor", i)));
+            }
+            path.push(i);
+            let node = self.item("nodes", i)?;
+            let matrix = mul(&parent, &Self::local_matrix(node));
+            if let Some(mesh) = node.get("mesh") {
+                let mesh = mesh
+                    .as_usize()
+                    .ok_or_else(|| self.error(format!("node {} has invalid mesh", i)))?;
+                let name = node
+                    .get("name")
+                    .or_else(|| self.items("meshes").get(mesh).and_then(|m| m.get("name")))
+                    .and_then(|n| n.as_str())
+                    .map_or_else(|| format!("mesh{}", mesh), |n| n.to_string());
+                meshes.push((mesh, name, matrix));
+            }
+            let children = Self::indices(node.get("children"));
+            stack.extend(children.iter().rev().map(|&c| (c, depth + 1, matrix)));
+        }
+        Ok(meshes)
+    }
+
+    fn add_primitive(
+        &self,
+        model: &mut WireframeModel,
+        primitive: &Json,
+        matrix: &Mat4,
+    ) -> Result<(), GltfError> {
+        let attributes = primitive.get("attributes").unwrap_or(&Json::Null);
+        let (positions, components) = self.accessor(self.index(attributes, "POSITION")?)?;
+        if components != 3 {
+            return Err(self.error("POSITION must be VEC3".to_string()));
+        }
+        let count = positions.len() / 3;
+        let vec3 = |v: &[f64]| Vec3 {
+            x: v[0] as f32,
+            y: v[1] as f32,
+            z: v[2] as f32,
+        };
+
+        let vertex_base = model.vertexes.len();
+        let points = positions
+            .chunks(3)
+            .map(|p| transform_point(matrix, vec3(p)));
+        model.vertexes.extend(points);
+
+        let norm_base = model.norm.len();
+        let has_norm = attributes.get("NORMAL").is_some();
+        if has_norm {
+            let (normals, _) = self.accessor(self.index(attributes, "NORMAL")?)?;
+            let normals = normals.chunks(3).map(|n| transform_normal(matrix, vec3(n)));
+            model.norm.extend(normals.take(count));
+        }
+
+        let texture_base = model.texture_coord.len();
+        let has_uv = attributes.get("TEXCOORD_0").is_some();
+        if has_uv {
+            let (uv, _) = self.accessor(self.index(attributes, "TEXCOORD_0")?)?;
+            // gltf `v` goes down, obj `v` goes up
+            let uv = uv.chunks(2).map(|t| (t[0] as f32, 1.0 - t[1] as f32));
+            model.texture_coord.extend(uv.take(count));
+        }
+        let complete = |base: usize, len: usize, present: bool| !present || len - base == count;
+        if !complete(norm_base, model.norm.len(), has_norm)
+            || !complete(texture_base, model.texture_coord.len(), has_uv)
+        {
+            return Err(self.error("attributes have different number of elements".to_string()));
+        }
+
+        let indices: Vec<usize> = match primitive.get("indices") {
+            Some(_) => {
+                let (indices, _) = self.accessor(self.index(primitive, "indices")?)?;
+                indices.iter().map(|&i| i as usize).collect()
+            }
+            None => (0..count).collect(),
+        };
+        if let Some(i) = indices.iter().find(|&&i| i >= count) {
+            return Err(self.error(format!("index {} out of range, only {} vertices", i, count)));
+        }
+        let triangles: Vec<[usize; 3]> = match primitive.get("mode").and_then(|m| m.as_usize()) {
+            None | Some(4) => indices
+                .chunks_exact(3)
+                .map(|t| [t[0], t[1], t[2]])
+                .collect(),
+            // strip, every second triangle has reversed winding order
+            Some(5) => (2..indices.len())
+                .map(|i| match i % 2 {
+                    0 => [indices[i - 2], indices[i - 1], indices[i]],
+                    _ => [indices[i - 1], indices[i - 2], indices[i]],
+                })
+                .collect(),
+            // fan
+            Some(6) => (2..indices.len())
+                .map(|i| [indices[0], indices[i - 1], indices[i]])
+                .collect(),
+            Some(mode) => return Err(self.error(format!("unsupported primitive mode {}", mode))),
+        };
+
+        let material = match primitive.get("material") {
+            Some(_) => Some(self.index(primitive, "material")?),
+            None => None,
+        };
+        if material.is_some_and(|m| m >= model.materials.len()) {
+            return Err(self.error(format!("material {:?} not found", material)));
+        }
+        // mirroring transformation flips front faces
+        let mirrored = determinant(matrix) < 0.0;
+        for t in triangles {
+            let t = if mirrored { [t[0], t[2], t[1]] } else { t };
+            let face = t.map(|i| Face {
+                vertex_index: vertex_base + i,
+                texture_index: has_uv.then(|| texture_base + i),
+                norm_index: has_norm.then(|| norm_base + i),
+            });
+            model.faces.push(face);
+            model.polygons.push(face.to_vec());
+            model.face_materials.push(material);
+        }
+        Ok(())
+    }
+}
+
+// Binary container: 12 bytes header and chunks, the first one is json
+fn parse_glb(path: &str, data: &[u8]) -> Result<(Vec<u8>, Option<Vec<u8>>), GltfError> {
+    let error = |message: &str| GltfError::Format {
+        path: path.to_string(),
+        message: message.to_string(),
+    };
+    let u32_at = |offset: usize| {
+        data.get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+    };
+    if u32_at(4) != Some(2) {
+        return Err(error("only glb version 2 is supported"));
+    }
+    let mut json = None;
+    let mut bin = None;
+    let mut offset = 12;
+    while let (Some(length), Some(chunk_type)) = (u32_at(offset), u32_at(offset + 4)) {
+        let chunk = data
+            .get(offset + 8..offset + 8 + length)
+            .ok_or_else(|| error("glb chunk is out of bounds"))?;
+        match chunk_type {
+            0x4E4F534A if json.is_none() => json = Some(chunk.to_vec()),
+            0x004E4942 if bin.is_none() => bin = Some(chunk.to_vec()),
+            _ => {}
+        }
+        offset += 8 + length;
+    }
+    Ok((json.ok_or_else(|| error("glb without json chunk"))?, bin))
+}
+
+impl WireframeModel {
+    /// Loads all meshes of the default scene, every mesh instance becomes a group.
+    pub fn from_gltf(path: String) -> Result<WireframeModel, GltfError> {
+        let data = fs::read(&path).map_err(|error| GltfError::Io {
+            path: path.clone(),
+            error,
+        })?;
+        let (text, bin) = if data.starts_with(b"glTF") {
+            parse_glb(&path, &data)?
+        } else {
+            (data, None)
+        };
+        let format_error = |message: String| GltfError::Format {
+            path: path.clone(),
+            message,
+        };
+        let text = String::from_utf8(text).map_err(|_| format_error("invalid utf8".to_string()))?;
+        let json = Json::parse(&text).map_err(|e| format_error(format!("invalid json, {}", e)))?;
+
+        let mut document = Document {
+            path: &path,
+            json,
+            buffers: vec![],
+        };
+        document.load_buffers(bin)?;
+
+        let mut model = WireframeModel {
+            materials: document.materials()?,
+            ..WireframeModel::default()
+        };
+        for (mesh, name, matrix) in document.meshes()? {
+            let mut group = Group::new(name, model.faces.len(), model.polygons.len());
+            let primitives = document.item("meshes", mesh)?.get("primitives");
+            for primitive in primitives.and_then(|p| p.as_array()).unwrap_or(EMPTY) {
+                document.add_primitive(&mut model, primitive, &matrix)?;
+            }
+            group.end(model.faces.len(), model.polygons.len());
+            model.groups.push(group);
+        }
+        Ok(model)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wireframe::write_test_file;
+
+    fn glb(json: &str, bin: &[u8]) -> Vec<u8> {
+        let mut json = json.as_bytes().to_vec();
+        while !json.len().is_multiple_of(4) {
+            json.push(b' ');
+        }
+        let length = 12 + 8 + json.len() + 8 + bin.len();
+        let mut data = vec![];
+        data.extend_from_slice(b"glTF");
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&(length as u32).to_le_bytes());
+        data.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        data.extend_from_slice(b"JSON");
+        data.extend_from_slice(&json);
+        data.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        data.extend_from_slice(b"BIN\0");
+        data.extend_from_slice(bin);
+        data
+    }
+
+    #[test]
+    fn test_base64_decode() {
+        assert_eq!(base64_decode("aGVsbG8="), Some(b"hello".to_vec()));
+        assert_eq!(base64_decode("AAEC/w=="), Some(vec![0, 1, 2, 255]));
+        assert_eq!(base64_decode("a*b"), None);
+    }
+
+    #[test]
+    fn test_from_glb() {
+        // quad with positions, uvs and u16 indices
+        let mut bin = vec![];
+        let positions = [
+            0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0,
+        ];
+        let uvs = [0.0f32, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0];
+        let indices = [0u16, 1, 2, 0, 2, 3];
+        positions
+            .iter()
+            .for_each(|v| bin.extend_from_slice(&v.to_le_bytes()));
+        uvs.iter()
+            .for_each(|v| bin.extend_from_slice(&v.to_le_bytes()));
+        indices
+            .iter()
+            .for_each(|v| bin.extend_from_slice(&v.to_le_bytes()));
+        let json = r#"{
+            "asset": {"version": "2.0"},
+            "scene": 0,
+            "scenes": [{"nodes": [0]}],
+            "nodes": [{"name": "root", "translation": [0, 0, -1], "children": [1]},
+                      {"name": "quad", "mesh": 0, "scale": [2, 2, 2]}],
+            "meshes": [{"primitives": [{"attributes": {"POSITION": 0, "TEXCOORD_0": 1},
+                                        "indices": 2, "material": 0}]}],
+            "materials": [{"name": "red", "pbrMetallicRoughness": {"baseColorFactor": [1, 0, 0, 0.5]}}],
+            "accessors": [{"bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3"},
+                          {"bufferView": 1, "componentType": 5126, "count": 4, "type": "VEC2"},
+                          {"bufferView": 2, "componentType": 5123, "count": 6, "type": "SCALAR"}],
+            "bufferViews": [{"buffer": 0, "byteOffset": 0, "byteLength": 48},
+                            {"buffer": 0, "byteOffset": 48, "byteLength": 32},
+                            {"buffer": 0, "byteOffset": 80, "byteLength": 12}],
+            "buffers": [{"byteLength": 92}]
+        }"#;
+        let path = write_test_file("quad.glb", glb(json, &bin));
+
+        let model = WireframeModel::from_gltf(path).unwrap();
+        assert_eq!(model.vertexes.len(), 4);
+        assert_eq!(
+            model.vertexes[2],
+            Vec3 {
+                x: 2.0,
+                y: 2.0,
+                z: -1.0
+            }
+        );
+        assert_eq!(model.texture_coord[0], (0.0, 0.0));
+        assert_eq!(model.faces.len(), 2);
+        assert_eq!(model.faces[1].map(|f| f.vertex_index), [0, 2, 3]);
+        assert_eq!(model.faces[1][2].texture_index, Some(3));
+        assert_eq!(model.faces[1][2].norm_index, None);
+        assert_eq!(model.face_materials, [Some(0), Some(0)]);
+        assert_eq!(model.materials[0].name, "red");
+        assert_eq!(model.materials[0].dissolve, 0.5);
+        assert_eq!(model.groups.len(), 1);
+        assert_eq!(model.groups[0].name, "quad");
+        assert_eq!(model.groups[0].face_indices().collect::<Vec<_>>(), [0, 1]);
+    }
+
+    #[test]
+    fn test_remote_uri() {
+        let json = r#"{"asset": {"version": "2.0"},
+                       "buffers": [{"uri": "https://example.com/a.bin", "byteLength": 4}]}"#;
+        let path = write_test_file("remote.gltf", json);
+        let error = WireframeModel::from_gltf(path.clone()).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "{}: only local files are supported, found `https://example.com/a.bin`",
+                path
+            )
+        );
+    }
+
+    #[test]
+    fn test_node_cycle() {
+        let json = r#"{"asset": {"version": "2.0"},
+                       "scenes": [{"nodes": [0]}],
+                       "nodes": [{"children": [1]}, {"mesh": 0, "children": [1, 0]}],
+                       "meshes": [{"primitives": []}]}"#;
+        let path = write_test_file("cycle.gltf", json);
+        let error = WireframeModel::from_gltf(path.clone()).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            format!("{}: node 1 is its own ancestor", path)
+        );
+
+        let json = r#"{"asset": {"version": "2.0"},
+                       "scenes": [{"nodes": [0]}],
+                       "nodes": [{"children": [0]}]}"#;
+        let path = write_test_file("self_cycle.gltf", json);
+        assert!(WireframeModel::from_gltf(path).is_err());
+    }
+
+    #[test]
+    fn test_out_of_bounds_accessor() {
+        let json = |offset: &str| {
+            format!(
+                r#"{{"asset": {{"version": "2.0"}},
+                    "meshes": [{{"primitives": [{{"attributes": {{"POSITION": 0}}}}]}}],
+                    "accessors": [{{"bufferView": 0, "byteOffset": {}, "componentType": 5126,
+                                    "count": 2, "type": "VEC3"}}],
+                    "bufferViews": [{{"buffer": 0, "byteLength": 24}}],
+                    "buffers": [{{"byteLength": 24}}]}}"#,
+                offset
+            )
+        };
+        let bin = [0; 24];
+        for offset in ["4", "18446744073709551615"] {
+            let path = write_test_file("accessor.glb", glb(&json(offset), &bin));
+            let error = WireframeModel::from_gltf(path.clone()).err().unwrap();
+            assert_eq!(
+                error.to_string(),
+                format!("{}: accessor 0 is out of bounds", path)
+            );
+        }
+        let path = write_test_file("accessor.glb", glb(&json("0"), &bin));
+        assert!(WireframeModel::from_gltf(path).is_ok());
+
+        // without a bufferView the accessor is zeros, its count is limited by the buffers
+        let zeros = |count: &str| {
+            json("0")
+                .replace(r#""POSITION": 0"#, r#""POSITION": 0, "NORMAL": 1"#)
+                .replace(
+                    r#""VEC3"}]"#,
+                    &format!(r#""VEC3"}}, {{"count": {}, "type": "VEC3"}}]"#, count),
+                )
+        };
+        let path = write_test_file("zeros.glb", glb(&zeros("1000000000000"), &bin));
+        let error = WireframeModel::from_gltf(path.clone()).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            format!("{}: accessor 1 is too large", path)
+        );
+        let path = write_test_file("zeros.glb", glb(&zeros("2"), &bin));
+        assert!(WireframeModel::from_gltf(path).is_ok());
+
+        // `\uD800\u0041` is an unpaired surrogate
+        let path = write_test_file(
+            "surrogate.gltf",
+            r#"{"asset": {"version": "\uD800\u0041"}}"#,
+        );
+        assert!(WireframeModel::from_gltf(path).is_ok());
+    }
+
+    #[test]
+    fn test_transform_normal() {
+        let matrix = trs([1.0, 2.0, 3.0], [0.0, 0.0, 0.0, 1.0], [2.0, 1.0, 1.0]);
+        let n = Vec3 {
+            x: 1.0,
+            y: 1.0,
+            z: 0.0,
+        };
+        let transformed = transform_normal(&matrix, n);
+        // stretching along x makes the normal steeper
+        assert!(transformed.y > transformed.x);
+        assert!((transformed.x * 2.0 - transformed.y).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,187 @@
+use crate::point::Vec3;
+use crate::rgb_image::{RGBColor, RGBImage};
+use crate::shader::{DepthBuffer, Shader};
+use crate::wireframe::WireframeModel;
+
+// High dynamic range rendering with tone mapping
+// https://learnopengl.com/Advanced-Lighting/HDR
+// https://knarkowicz.wordpress.com/2016/01/06/aces-filmic-tone-mapping-curve/
+
+/// Maps linear HDR values to the 0..1 range.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ToneMapping {
+    /// Values above 1 are clipped
+    Clamp,
+    /// `c / (1 + c)`
+    Reinhard,
+    /// Narkowicz fit of the ACES filmic curve
+    Aces,
+    /// `1 - exp(-c * exposure)`
+    Exposure(f32),
+}
+
+impl ToneMapping {
+    fn map(self, c: f32) -> f32 {
+        let c = c.max(0.0);
+        let mapped = match self {
+            ToneMapping::Clamp => c,
+            ToneMapping::Reinhard => c / (1.0 + c),
+            ToneMapping::Aces => (c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14),
+            ToneMapping::Exposure(exposure) => 1.0 - (-c * exposure).exp(),
+        };
+        mapped.clamp(0.0, 1.0)
+    }
+}
+
+// sRGB transfer functions
+// https://en.wikipedia.org/wiki/SRGB
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+impl RGBColor {
+    /// sRGB encoded color as linear `r`, `g`, `b` in 0..1 range.
+    pub(crate) fn to_linear(self) -> Vec3<f32> {
+        let channel = |c: u8| srgb_to_linear(c as f32 / 255.0);
+        Vec3 {
+            x: channel(self.r),
+            y: channel(self.g),
+            z: channel(self.b),
+        }
+    }
+}
+
+/// Render target with linear `r`, `g`, `b` stored as `x`, `y`, `z` for every pixel,
+/// values are not limited by 1 until the image is resolved.
+/// Light is accumulated linearly only by shaders overriding `Shader::fragment_hdr`,
+/// others give their clamped sRGB `fragment` colors decoded to linear.
+pub struct HDRImage {
+    pub pixels: Vec<Vec3<f32>>,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl HDRImage {
+    pub fn new(width: u16, height: u16) -> Self {
+        HDRImage {
+            pixels: vec![Vec3::default(); usize::from(width) * usize::from(height)],
+            width,
+            height,
+        }
+    }
+
+    /// Draws all faces except ones from hidden groups with `Shader::fragment_hdr`.
+    pub fn draw(&mut self, model: &WireframeModel, shader: &mut dyn Shader) -> DepthBuffer {
+        self.draw_faces(model, model.visible_faces(), shader)
+    }
+
+    /// Same as `RGBImage::draw_faces`.
+    pub fn draw_faces(
+        &mut self,
+        model: &WireframeModel,
+        faces: impl IntoIterator<Item = usize>,
+        shader: &mut dyn Shader,
+    ) -> DepthBuffer {
+        let width = usize::from(self.width);
+        let mut z_buffer = DepthBuffer::new(self.width, self.height);
+        z_buffer.rasterize(
+            model,
+            faces,
+            shader,
+            &mut |shader, p, bc| match shader.fragment_hdr(bc) {
+                Some(color) => {
+                    self.pixels[usize::from(p.x) + usize::from(p.y) * width] = color;
+                    true
+                }
+                None => false,
+            },
+        );
+        z_buffer
+    }
+
+    /// Tone maps every pixel to a displayable image, `srgb` encodes the result with
+    /// the sRGB gamma, otherwise linear values are stored as is.
+    pub fn resolve(&self, tone_mapping: ToneMapping, srgb: bool) -> RGBImage {
+        let channel = |c: f32| {
+            let c = tone_mapping.map(c);
+            let c = if srgb { linear_to_srgb(c) } else { c };
+            (c * 255.0).round() as u8
+        };
+        let pixels = self
+            .pixels
+            .iter()
+            .map(|c| RGBColor {
+                b: channel(c.z),
+                g: channel(c.y),
+                r: channel(c.x),
+            })
+            .collect();
+        RGBImage {
+            pixels,
+            width: self.width,
+            height: self.height,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tone_mapping() {
+        for tone_mapping in [
+            ToneMapping::Reinhard,
+            ToneMapping::Aces,
+            ToneMapping::Exposure(1.0),
+        ] {
+            assert_eq!(tone_mapping.map(0.0), 0.0);
+            assert!(tone_mapping.map(0.5) < tone_mapping.map(1.0));
+            assert!(tone_mapping.map(4.0) < tone_mapping.map(16.0));
+            assert!(tone_mapping.map(1000.0) <= 1.0);
+        }
+        assert_eq!(ToneMapping::Reinhard.map(1.0), 0.5);
+        assert_eq!(ToneMapping::Clamp.map(3.0), 1.0);
+        assert_eq!(ToneMapping::Clamp.map(-1.0), 0.0);
+    }
+
+    #[test]
+    fn test_resolve() {
+        let mut image = HDRImage::new(2, 1);
+        image.pixels[0] = Vec3 {
+            x: 0.0,
+            y: 0.5,
+            z: 1.0,
+        };
+        image.pixels[1] = Vec3 {
+            x: 8.0,
+            y: 8.0,
+            z: 8.0,
+        };
+        let linear = image.resolve(ToneMapping::Clamp, false);
+        assert_eq!(
+            (linear.pixels[0].r, linear.pixels[0].g, linear.pixels[0].b),
+            (0, 128, 255)
+        );
+        assert_eq!(linear.pixels[1].r, 255);
+        let srgb = image.resolve(ToneMapping::Clamp, true);
+        assert_eq!(srgb.pixels[0].g, 188);
+        // sRGB encoding and decoding are inverse
+        let color = srgb.pixels[0].to_linear();
+        assert!((color.y - 0.5).abs() < 0.01);
+        let reinhard = image.resolve(ToneMapping::Reinhard, false);
+        assert_eq!(reinhard.pixels[1].r, 227);
+    }
+}
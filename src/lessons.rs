@@ -1,5 +1,11 @@
+use crate::camera::{Camera, Projection};
+use crate::hdr::{HDRImage, ToneMapping};
+use crate::light::{Attenuation, Light, Phong, Shadow};
+use crate::line::LineStyle;
 use crate::point::{Point, Vec3};
 use crate::rgb_image::{RGBImage, BLACK_COLOR, GREEN_COLOR, RED_COLOR, WHITE_COLOR};
+use crate::rgba_image::{Blending, RGBAColor, RGBAImage};
+use crate::texture::Texture;
 use crate::wireframe::WireframeModel;
 
 fn lesson0() -> RGBImage {
@@ -18,8 +24,16 @@ fn lesson1() -> RGBImage {
 
 fn lesson1_1() -> RGBImage {
     let mut image = RGBImage::new(640, 640, BLACK_COLOR);
-    let model = WireframeModel::from_file("african_head.obj".to_string());
-    image.render_frame(model, WHITE_COLOR);
+    let model = WireframeModel::from_file("african_head.obj".to_string()).unwrap();
+    image.render_frame(model, WHITE_COLOR, LineStyle::Aliased);
+    image.flip_vertically();
+    return image;
+}
+
+fn lesson1_2() -> RGBImage {
+    let mut image = RGBImage::new(640, 640, BLACK_COLOR);
+    let model = WireframeModel::from_file("african_head.obj".to_string()).unwrap();
+    image.render_frame(model, WHITE_COLOR, LineStyle::AntiAliased);
     image.flip_vertically();
     return image;
 }
@@ -53,7 +67,7 @@ fn lesson2() -> RGBImage {
 
 fn lesson2_2() -> RGBImage {
     let mut image = RGBImage::new(640, 640, BLACK_COLOR);
-    let model = WireframeModel::from_file("african_head.obj".to_string());
+    let model = WireframeModel::from_file("african_head.obj".to_string()).unwrap();
     image.render_random(model);
     image.flip_vertically();
     return image;
@@ -61,7 +75,7 @@ fn lesson2_2() -> RGBImage {
 
 fn lesson2_3() -> RGBImage {
     let mut image = RGBImage::new(640, 640, BLACK_COLOR);
-    let model = WireframeModel::from_file("african_head.obj".to_string());
+    let model = WireframeModel::from_file("african_head.obj".to_string()).unwrap();
     image.render_light(
         model,
         Vec3 {
@@ -76,7 +90,7 @@ fn lesson2_3() -> RGBImage {
 
 fn lesson3_1() -> RGBImage {
     let mut image = RGBImage::new(640, 640, BLACK_COLOR);
-    let model = WireframeModel::from_file("african_head.obj".to_string());
+    let model = WireframeModel::from_file("african_head.obj".to_string()).unwrap();
     image.render_z_buffer(
         model,
         Vec3 {
@@ -91,8 +105,8 @@ fn lesson3_1() -> RGBImage {
 
 fn lesson3_2() -> RGBImage {
     let mut canvas = RGBImage::new(640, 640, BLACK_COLOR);
-    let texture = image::open("african_head_diffuse.tga").unwrap().flipv();
-    let model = WireframeModel::from_file("african_head.obj".to_string());
+    let texture = Texture::from(image::open("african_head_diffuse.tga").unwrap().flipv());
+    let model = WireframeModel::from_file("african_head.obj".to_string()).unwrap();
     canvas.render_z_buffer_texture(
         model,
         texture,
@@ -108,8 +122,8 @@ fn lesson3_2() -> RGBImage {
 
 fn lesson4() -> RGBImage {
     let mut canvas = RGBImage::new(640, 640, BLACK_COLOR);
-    let texture = image::open("african_head_diffuse.tga").unwrap().flipv();
-    let model = WireframeModel::from_file("african_head.obj".to_string());
+    let texture = Texture::from(image::open("african_head_diffuse.tga").unwrap().flipv());
+    let model = WireframeModel::from_file("african_head.obj".to_string()).unwrap();
     canvas.render_z_buffer_texture_perspective(
         model,
         texture,
@@ -118,6 +132,46 @@ fn lesson4() -> RGBImage {
             y: 1.0,
             z: -1.0,
         },
+        &Camera {
+            position: Vec3 {
+                x: 0.0,
+                y: 0.0,
+                z: 3.0,
+            },
+            ..Camera::default()
+        },
+    );
+    canvas.flip_vertically();
+    return canvas;
+}
+
+// Isometric view, parallel edges stay parallel
+fn lesson4_1() -> RGBImage {
+    let mut canvas = RGBImage::new(640, 640, BLACK_COLOR);
+    let texture = Texture::from(image::open("african_head_diffuse.tga").unwrap().flipv());
+    let model = WireframeModel::from_file("african_head.obj".to_string()).unwrap();
+    canvas.render_z_buffer_texture_perspective_gouraud(
+        model,
+        texture,
+        Vec3 {
+            x: 1.0,
+            y: -1.0,
+            z: 1.0,
+        },
+        &Camera {
+            position: Vec3 {
+                x: 3.0,
+                y: 3.0,
+                z: 3.0,
+            },
+            projection: Projection::Orthographic {
+                left: -1.2,
+                right: 1.2,
+                bottom: -1.2,
+                top: 1.2,
+            },
+            ..Camera::default()
+        },
     );
     canvas.flip_vertically();
     return canvas;
@@ -125,8 +179,8 @@ fn lesson4() -> RGBImage {
 
 fn lesson5() -> RGBImage {
     let mut canvas = RGBImage::new(640, 640, BLACK_COLOR);
-    let texture = image::open("african_head_diffuse.tga").unwrap().flipv();
-    let model = WireframeModel::from_file("african_head.obj".to_string());
+    let texture = Texture::from(image::open("african_head_diffuse.tga").unwrap().flipv());
+    let model = WireframeModel::from_file("african_head.obj".to_string()).unwrap();
     canvas.render_z_buffer_texture_perspective_gouraud(
         model,
         texture,
@@ -135,11 +189,245 @@ fn lesson5() -> RGBImage {
             y: -1.0,
             z: 1.0,
         },
+        &Camera::default(),
+    );
+    canvas.flip_vertically();
+    return canvas;
+}
+
+// Maps which aren't in the repository are optional, lessons render without them
+fn optional_texture(path: &str) -> Option<Texture> {
+    match image::open(path) {
+        Ok(image) => Some(Texture::from(image.flipv())),
+        Err(error) => {
+            println!("warning: {}: {}", path, error);
+            None
+        }
+    }
+}
+
+fn lesson6() -> RGBImage {
+    let mut canvas = RGBImage::new(640, 640, BLACK_COLOR);
+    let texture = Texture::from(image::open("african_head_diffuse.tga").unwrap().flipv());
+    // without the normal map the interpolated normal is used as is
+    let normal_map = optional_texture("african_head_nm_tangent.tga");
+    let model = WireframeModel::from_file("african_head.obj".to_string()).unwrap();
+    canvas.render_normal_mapping(
+        model,
+        texture,
+        normal_map,
+        Vec3 {
+            x: 1.0,
+            y: -1.0,
+            z: 1.0,
+        },
+        &Camera::default(),
     );
     canvas.flip_vertically();
     return canvas;
 }
 
+fn lesson6_1() -> RGBImage {
+    let mut canvas = RGBImage::new(640, 640, BLACK_COLOR);
+    let texture = Texture::from(image::open("african_head_diffuse.tga").unwrap().flipv());
+    // without the specular map `Phong::shininess` is used
+    let specular_map = optional_texture("african_head_spec.tga");
+    let model = WireframeModel::from_file("african_head.obj".to_string()).unwrap();
+    canvas.render_phong(
+        model,
+        texture,
+        specular_map,
+        Phong::default(),
+        &[Light::directional(Vec3 {
+            x: 1.0,
+            y: -1.0,
+            z: 1.0,
+        })],
+        &Camera::default(),
+    );
+    canvas.flip_vertically();
+    return canvas;
+}
+
+fn shadow_mapping(shadow_map: bool) -> RGBImage {
+    let mut canvas = RGBImage::new(640, 640, BLACK_COLOR);
+    let texture = Texture::from(image::open("african_head_diffuse.tga").unwrap().flipv());
+    // without the specular map `Phong::shininess` is used
+    let specular_map = optional_texture("african_head_spec.tga");
+    let model = WireframeModel::from_file("african_head.obj".to_string()).unwrap();
+    let depth = canvas.render_shadows(
+        model,
+        texture,
+        specular_map,
+        Phong::default(),
+        &[Light::directional(Vec3 {
+            x: 1.0,
+            y: -1.0,
+            z: 1.0,
+        })],
+        &Camera::default(),
+        Shadow::default(),
+    );
+    if shadow_map {
+        canvas = depth[0].to_image();
+    }
+    canvas.flip_vertically();
+    return canvas;
+}
+
+fn lesson7() -> RGBImage {
+    shadow_mapping(false)
+}
+
+fn lesson7_1() -> RGBImage {
+    shadow_mapping(true)
+}
+
+fn lesson8() -> RGBImage {
+    let mut canvas = RGBImage::new(640, 640, BLACK_COLOR);
+    let texture = Texture::from(image::open("african_head_diffuse.tga").unwrap().flipv());
+    let model = WireframeModel::from_file("african_head.obj".to_string()).unwrap();
+    let depth = canvas.render_z_buffer_texture_perspective_gouraud(
+        model,
+        texture,
+        Vec3 {
+            x: 1.0,
+            y: -1.0,
+            z: 1.0,
+        },
+        &Camera::default(),
+    );
+    canvas.apply_ambient_occlusion(&depth, 16, 1.0);
+    canvas.flip_vertically();
+    return canvas;
+}
+
+// Dim white, bright orange point and blue spot lights
+fn colored_lights() -> [Light; 3] {
+    [
+        Light::Directional {
+            direction: Vec3 {
+                x: 1.0,
+                y: -1.0,
+                z: 1.0,
+            },
+            color: Vec3 {
+                x: 0.4,
+                y: 0.4,
+                z: 0.4,
+            },
+        },
+        Light::Point {
+            position: Vec3 {
+                x: -1.5,
+                y: 0.0,
+                z: 1.0,
+            },
+            color: Vec3 {
+                x: 2.0,
+                y: 0.6,
+                z: 0.2,
+            },
+            attenuation: Attenuation {
+                constant: 1.0,
+                linear: 0.0,
+                quadratic: 0.5,
+            },
+        },
+        Light::Spot {
+            position: Vec3 {
+                x: 0.5,
+                y: 1.0,
+                z: 3.0,
+            },
+            direction: Vec3 {
+                x: -0.2,
+                y: -0.3,
+                z: -1.0,
+            },
+            color: Vec3 {
+                x: 0.3,
+                y: 0.5,
+                z: 1.5,
+            },
+            attenuation: Attenuation::default(),
+            inner_angle: 0.1,
+            outer_angle: 0.2,
+        },
+    ]
+}
+
+fn lesson8_1() -> RGBImage {
+    let mut canvas = RGBImage::new(640, 640, BLACK_COLOR);
+    let texture = Texture::from(image::open("african_head_diffuse.tga").unwrap().flipv());
+    let model = WireframeModel::from_file("african_head.obj".to_string()).unwrap();
+    canvas.render_phong(
+        model,
+        texture,
+        None,
+        Phong::default(),
+        &colored_lights(),
+        &Camera::default(),
+    );
+    canvas.flip_vertically();
+    return canvas;
+}
+
+// Same lights without clamping, bright highlights are compressed by the filmic curve
+fn lesson8_2() -> RGBImage {
+    let mut canvas = HDRImage::new(640, 640);
+    let texture = Texture::from(image::open("african_head_diffuse.tga").unwrap().flipv());
+    let model = WireframeModel::from_file("african_head.obj".to_string()).unwrap();
+    canvas.render_phong(
+        model,
+        texture,
+        None,
+        Phong::default(),
+        &colored_lights(),
+        &Camera::default(),
+    );
+    let mut canvas = canvas.resolve(ToneMapping::Aces, true);
+    canvas.flip_vertically();
+    return canvas;
+}
+
+fn lesson8_3() -> RGBImage {
+    let mut canvas = RGBAImage::new(
+        640,
+        640,
+        RGBAColor {
+            r: 0,
+            g: 0,
+            b: 0,
+            a: 0,
+        },
+    );
+    // half transparent texture, so faces behind are seen through
+    let mut texture = image::open("african_head_diffuse.tga")
+        .unwrap()
+        .flipv()
+        .to_rgba8();
+    for pixel in texture.pixels_mut() {
+        pixel.0[3] = 128;
+    }
+    let model = WireframeModel::from_file("african_head.obj".to_string()).unwrap();
+    canvas.render_transparent(
+        model,
+        Texture::from(image::DynamicImage::ImageRgba8(texture)),
+        Phong::default(),
+        &[Light::directional(Vec3 {
+            x: 1.0,
+            y: -1.0,
+            z: 1.0,
+        })],
+        &Camera::default(),
+        Blending::AlphaOver,
+    );
+    let mut canvas = canvas.to_rgb(BLACK_COLOR);
+    canvas.flip_vertically();
+    return canvas;
+}
+
 #[derive(Copy, Clone)]
 pub struct Lesson {
     pub name: &'static str,
@@ -152,7 +440,7 @@ impl Lesson {
     }
 }
 
-pub fn lessons() -> [Lesson; 10] {
+pub fn lessons() -> [Lesson; 20] {
     [
         Lesson {
             name: "Pixel",
@@ -166,6 +454,10 @@ pub fn lessons() -> [Lesson; 10] {
             name: "Wireframe",
             renderer: lesson1_1,
         },
+        Lesson {
+            name: "Anti-aliased wireframe",
+            renderer: lesson1_2,
+        },
         Lesson {
             name: "Triangles",
             renderer: lesson2,
@@ -190,9 +482,45 @@ pub fn lessons() -> [Lesson; 10] {
             name: "Perspective projection",
             renderer: lesson4,
         },
+        Lesson {
+            name: "Orthographic projection",
+            renderer: lesson4_1,
+        },
         Lesson {
             name: "Gouraud shading",
             renderer: lesson5,
         },
+        Lesson {
+            name: "Normal mapping",
+            renderer: lesson6,
+        },
+        Lesson {
+            name: "Phong shading",
+            renderer: lesson6_1,
+        },
+        Lesson {
+            name: "Shadow mapping",
+            renderer: lesson7,
+        },
+        Lesson {
+            name: "Shadow map",
+            renderer: lesson7_1,
+        },
+        Lesson {
+            name: "Ambient occlusion",
+            renderer: lesson8,
+        },
+        Lesson {
+            name: "Multiple lights",
+            renderer: lesson8_1,
+        },
+        Lesson {
+            name: "Tone mapping",
+            renderer: lesson8_2,
+        },
+        Lesson {
+            name: "Transparency",
+            renderer: lesson8_3,
+        },
     ]
 }
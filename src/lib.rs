@@ -1,9 +1,25 @@
+mod ambient_occlusion;
+pub mod camera;
+mod clipping;
+pub mod gltf;
+pub mod hdr;
+mod json;
 pub mod lessons;
-mod line;
+pub mod light;
+pub mod line;
+pub mod loader;
+pub mod material;
 mod matrix;
-mod point;
+mod normals;
+mod ply;
+pub mod point;
+mod polygon;
 mod rendering;
 pub mod rgb_image;
+pub mod rgba_image;
+pub mod shader;
+mod stl;
+pub mod texture;
 mod tga_image;
 mod triangle;
-mod wireframe;
+pub mod wireframe;
@@ -0,0 +1,203 @@
+use crate::point::{diff, dot_product, Vec3};
+
+// Phong reflection model
+// https://en.wikipedia.org/wiki/Phong_reflection_model
+
+/// Surface parameters of the Phong shading.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Phong {
+    /// Added once regardless of the lights, it is not tinted by light colors
+    pub ambient: f32,
+    pub diffuse: f32,
+    pub specular: f32,
+    /// Specular exponent used when there is no specular map
+    pub shininess: f32,
+    /// Blinn-Phong halfway vector instead of the reflected light direction
+    pub blinn: bool,
+}
+
+impl Default for Phong {
+    fn default() -> Self {
+        Phong {
+            ambient: 0.05,
+            diffuse: 1.0,
+            specular: 0.6,
+            shininess: 16.0,
+            blinn: false,
+        }
+    }
+}
+
+/// Intensity of point and spot lights is divided by `constant + linear * d + quadratic * d²`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Attenuation {
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+impl Default for Attenuation {
+    fn default() -> Self {
+        Attenuation {
+            constant: 1.0,
+            linear: 0.0,
+            quadratic: 0.0,
+        }
+    }
+}
+
+/// Light source, colors are linear `r`, `g`, `b` values stored as `x`, `y`, `z`,
+/// values above 1 make the light brighter.
+///
+/// Only Phong shading takes a list of lights, flat, Gouraud and normal mapping
+/// lessons keep a single white `light_dir`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Light {
+    /// Infinitely far light, `direction` points to the light
+    Directional {
+        direction: Vec3<f32>,
+        color: Vec3<f32>,
+    },
+    Point {
+        position: Vec3<f32>,
+        color: Vec3<f32>,
+        attenuation: Attenuation,
+    },
+    /// Point light shining along `direction` inside a cone, intensity fades out
+    /// between `inner_angle` and `outer_angle` (in radians) from its axis
+    Spot {
+        position: Vec3<f32>,
+        direction: Vec3<f32>,
+        color: Vec3<f32>,
+        attenuation: Attenuation,
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+}
+
+impl Light {
+    /// White directional light.
+    pub fn directional(direction: Vec3<f32>) -> Self {
+        Light::Directional {
+            direction,
+            color: Vec3 {
+                x: 1.0,
+                y: 1.0,
+                z: 1.0,
+            },
+        }
+    }
+
+    /// Unit direction from the point `p` to the light and the light color reaching it.
+    pub fn illuminate(&self, p: Vec3<f32>) -> (Vec3<f32>, Vec3<f32>) {
+        let scaled = |c: Vec3<f32>, k: f32| Vec3 {
+            x: c.x * k,
+            y: c.y * k,
+            z: c.z * k,
+        };
+        let point = |position: Vec3<f32>, attenuation: &Attenuation| {
+            let to_light = diff(position, p);
+            let d = dot_product(to_light, to_light).sqrt();
+            let k = attenuation.constant + attenuation.linear * d + attenuation.quadratic * d * d;
+            (to_light.normalized(), 1.0 / k)
+        };
+        match self {
+            Light::Directional { direction, color } => (direction.normalized(), *color),
+            Light::Point {
+                position,
+                color,
+                attenuation,
+            } => {
+                let (l, k) = point(*position, attenuation);
+                (l, scaled(*color, k))
+            }
+            Light::Spot {
+                position,
+                direction,
+                color,
+                attenuation,
+                inner_angle,
+                outer_angle,
+            } => {
+                let (l, k) = point(*position, attenuation);
+                let cos = -dot_product(l, direction.normalized());
+                let (inner, outer) = (inner_angle.cos(), outer_angle.cos());
+                let cone = ((cos - outer) / (inner - outer).max(f32::EPSILON)).clamp(0.0, 1.0);
+                (l, scaled(*color, k * cone))
+            }
+        }
+    }
+}
+
+/// Shadow mapping parameters, the shadow map has the size of the rendered image.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Shadow {
+    /// Depth offset against self shadowing (shadow acne), in depth buffer units
+    pub bias: f32,
+    /// Percentage closer filtering radius in shadow map pixels, 0 gives hard edges
+    pub pcf_radius: u16,
+}
+
+impl Default for Shadow {
+    fn default() -> Self {
+        Shadow {
+            bias: 2.0,
+            pcf_radius: 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_illuminate() {
+        let white = Vec3 {
+            x: 1.0,
+            y: 1.0,
+            z: 1.0,
+        };
+        let origin = Vec3::default();
+        let point = Light::Point {
+            position: Vec3 {
+                x: 0.0,
+                y: 2.0,
+                z: 0.0,
+            },
+            color: white,
+            attenuation: Attenuation {
+                constant: 0.0,
+                linear: 0.0,
+                quadratic: 1.0,
+            },
+        };
+        let (l, color) = point.illuminate(origin);
+        assert_eq!(
+            l,
+            Vec3 {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0
+            }
+        );
+        assert_eq!(color.x, 0.25);
+
+        let spot = |x: f32| Light::Spot {
+            position: Vec3 { x, y: 1.0, z: 0.0 },
+            direction: Vec3 {
+                x: 0.0,
+                y: -1.0,
+                z: 0.0,
+            },
+            color: white,
+            attenuation: Attenuation::default(),
+            inner_angle: 0.1,
+            outer_angle: 0.5,
+        };
+        assert_eq!(spot(0.0).illuminate(origin).1, white);
+        assert_eq!(spot(1.0).illuminate(origin).1, Vec3::default());
+        let fading = spot(0.3).illuminate(origin).1.x;
+        assert!(fading > 0.0 && fading < 1.0);
+    }
+}
@@ -1,11 +1,84 @@
-use crate::point::Point;
+use crate::point::{Point, Vec2};
 use crate::rgb_image::RGBColor;
 use crate::rgb_image::RGBImage;
+use std::collections::HashMap;
 use std::mem::swap;
 
 // Lesson 1: Bresenham’s Line Drawing Algorithm
 // https://github.com/ssloy/tinyrenderer/wiki/Lesson-1:-Bresenham’s-Line-Drawing-Algorithm
 
+/// How lines of wireframes are drawn.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LineStyle {
+    /// 1 pixel wide Bresenham line
+    Aliased,
+    /// 1 pixel wide Xiaolin Wu line, pixels are blended by the line coverage
+    AntiAliased,
+    /// Anti-aliased line of the given width in pixels with round caps and joins
+    Thick(f32),
+}
+
+fn fract(x: f32) -> f32 {
+    x - x.floor()
+}
+
+fn distance_to_segment(p: Vec2<f32>, a: Vec2<f32>, b: Vec2<f32>) -> f32 {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let length2 = dx * dx + dy * dy;
+    let t = if length2 > 0.0 {
+        (((p.x - a.x) * dx + (p.y - a.y) * dy) / length2).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let (x, y) = (a.x + dx * t - p.x, a.y + dy * t - p.y);
+    (x * x + y * y).sqrt()
+}
+
+// Liang–Barsky clipping of the segment by the rectangle `min..=max`,
+// `None` if the segment is outside of it
+// https://en.wikipedia.org/wiki/Liang%E2%80%93Barsky_algorithm
+pub(crate) fn clip_line(
+    start: Vec2<f32>,
+    end: Vec2<f32>,
+    min: Vec2<f32>,
+    max: Vec2<f32>,
+) -> Option<(Vec2<f32>, Vec2<f32>)> {
+    if ![start.x, start.y, end.x, end.y]
+        .iter()
+        .all(|c| c.is_finite())
+    {
+        return None;
+    }
+    let (dx, dy) = (end.x - start.x, end.y - start.y);
+    let (mut t0, mut t1) = (0.0_f32, 1.0_f32);
+    // the point at `t` is inside when `p * t <= q` for every edge
+    for (p, q) in [
+        (-dx, start.x - min.x),
+        (dx, max.x - start.x),
+        (-dy, start.y - min.y),
+        (dy, max.y - start.y),
+    ] {
+        if p == 0.0 {
+            // parallel to the edge
+            if q < 0.0 {
+                return None;
+            }
+        } else if p < 0.0 {
+            t0 = t0.max(q / p);
+        } else {
+            t1 = t1.min(q / p);
+        }
+    }
+    if t0 > t1 {
+        return None;
+    }
+    let at = |t: f32| Vec2 {
+        x: start.x + dx * t,
+        y: start.y + dy * t,
+    };
+    Some((at(t0), at(t1)))
+}
+
 impl RGBImage {
     /*
     pub(crate) fn line1(&mut self, start: Point, end: Point, color: RGBColor) {
@@ -20,10 +93,35 @@ impl RGBImage {
     */
 
     pub(crate) fn line(&mut self, start: Point, end: Point, color: RGBColor) {
-        let mut x0: i32 = start.x as i32;
-        let mut y0: i32 = start.y as i32;
-        let mut x1: i32 = end.x as i32;
-        let mut y1: i32 = end.y as i32;
+        let point = |p: Point| Vec2 {
+            x: p.x as f32,
+            y: p.y as f32,
+        };
+        self.line_clipped(point(start), point(end), color);
+    }
+
+    // Same as `line` for endpoints anywhere, integer parts of coordinates are pixels
+    // and only the part of the line inside of the image is drawn
+    pub(crate) fn line_clipped(&mut self, start: Vec2<f32>, end: Vec2<f32>, color: RGBColor) {
+        let pixel = |p: Vec2<f32>| Vec2 {
+            x: p.x.floor(),
+            y: p.y.floor(),
+        };
+        let max = Vec2 {
+            x: self.width as f32 - 1.0,
+            y: self.height as f32 - 1.0,
+        };
+        let (start, end) = match clip_line(pixel(start), pixel(end), Vec2::default(), max) {
+            Some(line) => line,
+            None => return,
+        };
+        // rounding errors of clipped endpoints can't get them out of the image
+        let x = |p: Vec2<f32>| (p.x.round() as i32).clamp(0, self.width as i32 - 1);
+        let y = |p: Vec2<f32>| (p.y.round() as i32).clamp(0, self.height as i32 - 1);
+        let mut x0: i32 = x(start);
+        let mut y0: i32 = y(start);
+        let mut x1: i32 = x(end);
+        let mut y1: i32 = y(end);
         let steep;
         if (x1 - x0).abs() < (y1 - y0).abs() {
             swap(&mut x0, &mut y0);
@@ -60,4 +158,237 @@ impl RGBImage {
             }
         }
     }
+
+    // Xiaolin Wu's line algorithm, integer coordinates are pixel centers
+    // https://en.wikipedia.org/wiki/Xiaolin_Wu%27s_line_algorithm
+    pub(crate) fn line_aa(&mut self, start: Vec2<f32>, end: Vec2<f32>, color: RGBColor) {
+        // the line covers pixels next to it, so it's clipped by the image with a margin
+        let min = Vec2 { x: -1.0, y: -1.0 };
+        let max = Vec2 {
+            x: self.width as f32,
+            y: self.height as f32,
+        };
+        let (start, end) = match clip_line(start, end, min, max) {
+            Some(line) => line,
+            None => return,
+        };
+        let (mut x0, mut y0, mut x1, mut y1) = (start.x, start.y, end.x, end.y);
+        let steep = (y1 - y0).abs() > (x1 - x0).abs();
+        if steep {
+            swap(&mut x0, &mut y0);
+            swap(&mut x1, &mut y1);
+        }
+        if x0 > x1 {
+            swap(&mut x0, &mut x1);
+            swap(&mut y0, &mut y1);
+        }
+        let dx = x1 - x0;
+        let gradient = if dx == 0.0 { 1.0 } else { (y1 - y0) / dx };
+        let mut plot = |x: f32, y: f32, coverage: f32| {
+            let (x, y) = (x as i32, y as i32);
+            if steep {
+                self.blend_pixel(y, x, color, coverage);
+            } else {
+                self.blend_pixel(x, y, color, coverage);
+            }
+        };
+
+        // endpoints are weighted by the part of their pixel covered along `x`
+        let x_start = x0.round();
+        let y_start = y0 + gradient * (x_start - x0);
+        let gap = 1.0 - fract(x0 + 0.5);
+        plot(x_start, y_start.floor(), (1.0 - fract(y_start)) * gap);
+        plot(x_start, y_start.floor() + 1.0, fract(y_start) * gap);
+
+        let x_end = x1.round();
+        let y_end = y1 + gradient * (x_end - x1);
+        let gap = fract(x1 + 0.5);
+        plot(x_end, y_end.floor(), (1.0 - fract(y_end)) * gap);
+        plot(x_end, y_end.floor() + 1.0, fract(y_end) * gap);
+
+        // every column between them has two pixels sharing the coverage
+        let mut y = y_start + gradient;
+        for x in (x_start as i32 + 1)..(x_end as i32) {
+            plot(x as f32, y.floor(), 1.0 - fract(y));
+            plot(x as f32, y.floor() + 1.0, fract(y));
+            y += gradient;
+        }
+    }
+
+    // Segments are capsules, every pixel takes the largest coverage and the color of
+    // the segment covering it most, so joins are round and pixels shared by segments
+    // are blended once
+    pub(crate) fn thick_lines(
+        &mut self,
+        segments: &[(Vec2<f32>, Vec2<f32>, RGBColor)],
+        width: f32,
+    ) {
+        let radius = width / 2.0;
+        let (max_x, max_y) = (self.width as f32 - 1.0, self.height as f32 - 1.0);
+        let mut coverage: HashMap<(i32, i32), (f32, RGBColor)> = HashMap::new();
+        for &(a, b, color) in segments {
+            // bounding box of the capsule inside of the image
+            let x0 = (a.x.min(b.x) - radius - 1.0).floor().max(0.0) as i32;
+            let x1 = (a.x.max(b.x) + radius + 1.0).ceil().min(max_x) as i32;
+            let y0 = (a.y.min(b.y) - radius - 1.0).floor().max(0.0) as i32;
+            let y1 = (a.y.max(b.y) + radius + 1.0).ceil().min(max_y) as i32;
+            for y in y0..=y1 {
+                for x in x0..=x1 {
+                    let p = Vec2 {
+                        x: x as f32,
+                        y: y as f32,
+                    };
+                    let c = (radius + 0.5 - distance_to_segment(p, a, b)).clamp(0.0, 1.0);
+                    let pixel = coverage.entry((x, y)).or_insert((0.0, color));
+                    if c > pixel.0 {
+                        *pixel = (c, color);
+                    }
+                }
+            }
+        }
+        for ((x, y), (c, color)) in coverage {
+            if c > 0.0 {
+                self.blend_pixel(x, y, color, c);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rgb_image::{BLACK_COLOR, RED_COLOR, WHITE_COLOR};
+
+    fn gray(image: &RGBImage, x: usize, y: usize) -> u8 {
+        image.pixels[x + y * usize::from(image.width)].g
+    }
+
+    #[test]
+    fn test_clip_line() {
+        let point = |x: f32, y: f32| Vec2 { x, y };
+        let (min, max) = (point(0.0, 0.0), point(9.0, 9.0));
+        let inside = (point(1.0, 2.0), point(8.0, 3.0));
+        assert_eq!(clip_line(inside.0, inside.1, min, max), Some(inside));
+        assert_eq!(
+            clip_line(point(-5.0, 4.0), point(15.0, 4.0), min, max),
+            Some((point(0.0, 4.0), point(9.0, 4.0)))
+        );
+        assert_eq!(
+            clip_line(point(-2.0, -2.0), point(4.0, 4.0), min, max),
+            Some((point(0.0, 0.0), point(4.0, 4.0)))
+        );
+        assert_eq!(
+            clip_line(point(-5.0, 4.0), point(-1.0, 20.0), min, max),
+            None
+        );
+        assert_eq!(
+            clip_line(point(5.0, 10.0), point(12.0, 10.0), min, max),
+            None
+        );
+        assert_eq!(
+            clip_line(point(0.0, 20.0), point(20.0, 0.0), min, max),
+            None
+        );
+        assert_eq!(
+            clip_line(point(f32::NAN, 1.0), point(2.0, 2.0), min, max),
+            None
+        );
+    }
+
+    #[test]
+    fn test_line_clipped() {
+        let mut image = RGBImage::new(10, 10, BLACK_COLOR);
+        image.line_clipped(
+            Vec2 { x: -50.0, y: 5.0 },
+            Vec2 { x: 1e9, y: 5.0 },
+            WHITE_COLOR,
+        );
+        assert!((0..10).all(|x| gray(&image, x, 5) == 255));
+        assert_eq!(gray(&image, 0, 4), 0);
+
+        let mut image = RGBImage::new(10, 10, BLACK_COLOR);
+        image.line_clipped(
+            Vec2 { x: -3.0, y: -3.0 },
+            Vec2 { x: 12.0, y: 12.0 },
+            WHITE_COLOR,
+        );
+        assert!((0..10).all(|i| gray(&image, i, i) == 255));
+        assert_eq!(gray(&image, 0, 1), 0);
+
+        // outside or not finite
+        image.line_clipped(
+            Vec2 { x: 20.0, y: 0.0 },
+            Vec2 { x: 20.0, y: 9.0 },
+            RED_COLOR,
+        );
+        image.line_clipped(
+            Vec2 {
+                x: f32::NAN,
+                y: 0.0,
+            },
+            Vec2 { x: 5.0, y: 9.0 },
+            RED_COLOR,
+        );
+        image.line(Point { x: 3, y: 40 }, Point { x: 200, y: 9 }, RED_COLOR);
+        image.line_aa(Vec2 { x: -1e9, y: 3.0 }, Vec2 { x: 1e9, y: 3.5 }, RED_COLOR);
+        assert_eq!(image.pixels[3 * 10 + 5].g, 0);
+    }
+
+    #[test]
+    fn test_line_aa() {
+        let mut image = RGBImage::new(8, 8, BLACK_COLOR);
+        image.line_aa(
+            Vec2 { x: 1.0, y: 2.5 },
+            Vec2 { x: 6.0, y: 2.5 },
+            WHITE_COLOR,
+        );
+        // the line is between two rows, both are half covered
+        for x in 2..6 {
+            assert_eq!((gray(&image, x, 2), gray(&image, x, 3)), (128, 128));
+        }
+        assert_eq!(gray(&image, 1, 1), 0);
+        assert_eq!(gray(&image, 1, 2), 64);
+
+        let mut image = RGBImage::new(8, 8, BLACK_COLOR);
+        image.line_aa(
+            Vec2 { x: 3.0, y: 0.0 },
+            Vec2 { x: 3.0, y: 7.0 },
+            WHITE_COLOR,
+        );
+        assert_eq!(gray(&image, 3, 4), 255);
+        assert_eq!(gray(&image, 4, 4), 0);
+
+        // partially outside of the image
+        image.line_aa(
+            Vec2 { x: -5.0, y: -3.0 },
+            Vec2 { x: 12.0, y: 9.5 },
+            WHITE_COLOR,
+        );
+    }
+
+    #[test]
+    fn test_thick_lines() {
+        let mut image = RGBImage::new(20, 20, BLACK_COLOR);
+        let color = RGBColor {
+            r: 100,
+            g: 100,
+            b: 100,
+        };
+        let points =
+            [(2.0, 2.0), (15.0, 2.0), (15.0, 15.0), (-10.0, 30.0)].map(|(x, y)| Vec2 { x, y });
+        let segments: Vec<_> = points.windows(2).map(|s| (s[0], s[1], color)).collect();
+        image.thick_lines(&segments, 4.0);
+        // inside of the line and the join
+        assert_eq!(gray(&image, 8, 2), 100);
+        assert_eq!(gray(&image, 15, 2), 100);
+        assert_eq!(gray(&image, 16, 1), 100);
+        // anti-aliased edge
+        assert_eq!(gray(&image, 8, 4), 50);
+        assert_eq!(gray(&image, 8, 5), 0);
+        assert_eq!(gray(&image, 17, 2), 50);
+        // covered by both segments near the join, but blended once
+        assert_eq!(gray(&image, 13, 4), 50);
+        // the round join doesn't cover the outer corner
+        assert!(gray(&image, 17, 0) < 100);
+    }
 }
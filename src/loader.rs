@@ -0,0 +1,154 @@
+use crate::gltf::GltfError;
+use crate::wireframe::{ObjError, WireframeModel};
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Error of binary or simple text mesh formats (STL, PLY).
+#[derive(Debug)]
+pub enum MeshError {
+    Io { path: String, error: std::io::Error },
+    Format { path: String, message: String },
+}
+
+impl Display for MeshError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MeshError::Io { path, error } => write!(f, "{}: {}", path, error),
+            MeshError::Format { path, message } => write!(f, "{}: {}", path, message),
+        }
+    }
+}
+
+impl std::error::Error for MeshError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MeshError::Io { error, .. } => Some(error),
+            MeshError::Format { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ModelError {
+    Obj(ObjError),
+    Gltf(GltfError),
+    Mesh(MeshError),
+}
+
+impl Display for ModelError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModelError::Obj(error) => error.fmt(f),
+            ModelError::Gltf(error) => error.fmt(f),
+            ModelError::Mesh(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ModelError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ModelError::Obj(error) => error.source(),
+            ModelError::Gltf(error) => error.source(),
+            ModelError::Mesh(error) => error.source(),
+        }
+    }
+}
+
+impl From<ObjError> for ModelError {
+    fn from(error: ObjError) -> Self {
+        ModelError::Obj(error)
+    }
+}
+
+impl From<GltfError> for ModelError {
+    fn from(error: GltfError) -> Self {
+        ModelError::Gltf(error)
+    }
+}
+
+impl From<MeshError> for ModelError {
+    fn from(error: MeshError) -> Self {
+        ModelError::Mesh(error)
+    }
+}
+
+#[derive(PartialEq, Debug)]
+enum Format {
+    Obj,
+    Gltf,
+    Stl,
+    Ply,
+}
+
+impl Format {
+    fn from_extension(path: &str) -> Option<Format> {
+        let extension = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "obj" => Some(Format::Obj),
+            "gltf" | "glb" => Some(Format::Gltf),
+            "stl" => Some(Format::Stl),
+            "ply" => Some(Format::Ply),
+            _ => None,
+        }
+    }
+
+    // ASCII STL starts with `solid`, binary one has no magic at all, so it's the last resort
+    fn from_magic(magic: &[u8]) -> Format {
+        if magic.starts_with(b"glTF") || magic.starts_with(b"{") {
+            Format::Gltf
+        } else if magic.starts_with(b"ply") {
+            Format::Ply
+        } else if magic.starts_with(b"solid") || !magic.is_ascii() {
+            Format::Stl
+        } else {
+            Format::Obj
+        }
+    }
+}
+
+impl WireframeModel {
+    /// Loads OBJ, glTF, STL or PLY model picking the format by file extension
+    /// or by the first bytes of the file if extension is unknown.
+    pub fn from_file(path: String) -> Result<WireframeModel, ModelError> {
+        let format = match Format::from_extension(&path) {
+            Some(format) => format,
+            None => {
+                let mut magic = vec![];
+                let io_error = |error| MeshError::Io {
+                    path: path.clone(),
+                    error,
+                };
+                let file = File::open(&path).map_err(io_error)?;
+                file.take(80).read_to_end(&mut magic).map_err(io_error)?;
+                Format::from_magic(&magic)
+            }
+        };
+        Ok(match format {
+            Format::Obj => WireframeModel::from_obj(path)?,
+            Format::Gltf => WireframeModel::from_gltf(path)?,
+            Format::Stl => WireframeModel::from_stl(path)?,
+            Format::Ply => WireframeModel::from_ply(path)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_detection() {
+        assert_eq!(Format::from_extension("a/b/head.OBJ"), Some(Format::Obj));
+        assert_eq!(Format::from_extension("scene.glb"), Some(Format::Gltf));
+        assert_eq!(Format::from_extension("part.stl"), Some(Format::Stl));
+        assert_eq!(Format::from_extension("scan"), None);
+        assert_eq!(Format::from_magic(b"ply\nformat ascii 1.0"), Format::Ply);
+        assert_eq!(Format::from_magic(b"solid cube\n"), Format::Stl);
+        assert_eq!(Format::from_magic(&[0x00, 0xff, 0x10]), Format::Stl);
+        assert_eq!(Format::from_magic(b"glTF\x02\x00\x00\x00"), Format::Gltf);
+        assert_eq!(Format::from_magic(b"# Blender\nv 0 0 0"), Format::Obj);
+    }
+}
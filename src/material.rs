@@ -0,0 +1,225 @@
+use crate::point::Vec3;
+use crate::texture::Texture;
+use crate::wireframe::{float_field, read_lines, relative_path, FieldError, ObjError, ObjLine};
+
+// Wavefront material library
+// http://paulbourke.net/dataformats/mtl/
+
+/// Colors are linear `r`, `g`, `b` values stored as `x`, `y`, `z` in 0..1 range.
+pub struct Material {
+    pub name: String,
+    /// Ka
+    pub ambient: Vec3<f32>,
+    /// Kd
+    pub diffuse: Vec3<f32>,
+    /// Ks
+    pub specular: Vec3<f32>,
+    /// Ns, specular exponent
+    pub shininess: f32,
+    /// d, 1.0 is fully opaque
+    pub dissolve: f32,
+    /// illum
+    pub illumination: u8,
+    /// map_Kd
+    pub diffuse_map: Option<Texture>,
+    /// map_Bump
+    pub bump_map: Option<Texture>,
+    /// map_Ks
+    pub specular_map: Option<Texture>,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        let gray = |v| Vec3 { x: v, y: v, z: v };
+        Material {
+            name: String::new(),
+            ambient: gray(0.0),
+            diffuse: gray(1.0),
+            specular: gray(0.0),
+            shininess: 1.0,
+            dissolve: 1.0,
+            illumination: 2,
+            diffuse_map: None,
+            bump_map: None,
+            specular_map: None,
+        }
+    }
+}
+
+impl Material {
+    fn color_from_str(s: &str) -> Result<Vec3<f32>, FieldError> {
+        let mut it = s.split_ascii_whitespace();
+        let r = float_field(s, &mut it, "red value")?;
+        // `Kd 0.5` is a shortcut for `Kd 0.5 0.5 0.5`
+        if it.clone().next().is_none() {
+            return Ok(Vec3 { x: r, y: r, z: r });
+        }
+        let g = float_field(s, &mut it, "green value")?;
+        let b = float_field(s, &mut it, "blue value")?;
+        Ok(Vec3 { x: r, y: g, z: b })
+    }
+
+    fn value_from_str(s: &str) -> Result<f32, FieldError> {
+        float_field(s, &mut s.split_ascii_whitespace(), "value")
+    }
+
+    // Texture maps are flipped, so `v` texture coordinate goes up as in obj files,
+    // a texture which can't be loaded is skipped with a warning
+    fn texture_from_line(path: &str, line: &ObjLine) -> Result<Option<Texture>, ObjError> {
+        // file name is the last field, options before it are ignored
+        let file = line.fields.split_ascii_whitespace().last().ok_or_else(|| {
+            line.error(FieldError::new(
+                line.fields.len(),
+                "expected texture file".to_string(),
+            ))
+        })?;
+        let texture_path = relative_path(path, file);
+        match image::open(&texture_path) {
+            Ok(texture) => Ok(Some(Texture::from(texture.flipv()))),
+            Err(error) => {
+                line.warning(ObjError::Image {
+                    path: texture_path,
+                    error,
+                });
+                Ok(None)
+            }
+        }
+    }
+
+    /// Reads all materials from the `.mtl` file, texture maps are loaded relative to it.
+    pub fn from_file(path: String) -> Result<Vec<Material>, ObjError> {
+        let mut materials: Vec<Material> = vec![];
+        read_lines(&path, |line| {
+            if line.keyword == "newmtl" {
+                materials.push(Material {
+                    name: line.fields.trim().to_string(),
+                    ..Material::default()
+                });
+                return Ok(());
+            }
+            let material = materials
+                .last_mut()
+                .ok_or_else(|| line.error_at(line.keyword, "expected newmtl before".to_string()))?;
+            let parse_error = |e| line.error(e);
+            match line.keyword {
+                "Ka" => {
+                    material.ambient = Self::color_from_str(line.fields).map_err(parse_error)?
+                }
+                "Kd" => {
+                    material.diffuse = Self::color_from_str(line.fields).map_err(parse_error)?
+                }
+                "Ks" => {
+                    material.specular = Self::color_from_str(line.fields).map_err(parse_error)?
+                }
+                "Ns" => {
+                    material.shininess = Self::value_from_str(line.fields).map_err(parse_error)?
+                }
+                "d" => {
+                    material.dissolve = Self::value_from_str(line.fields).map_err(parse_error)?
+                }
+                "Tr" => {
+                    material.dissolve =
+                        1.0 - Self::value_from_str(line.fields).map_err(parse_error)?
+                }
+                "illum" => {
+                    let token = line.fields.trim();
+                    material.illumination = token.parse::<u8>().map_err(|_| {
+                        line.error_at(
+                            token,
+                            format!("expected illumination model, found `{}`", token),
+                        )
+                    })?;
+                }
+                "map_Kd" => material.diffuse_map = Self::texture_from_line(&path, line)?,
+                "map_Bump" | "map_bump" | "bump" => {
+                    material.bump_map = Self::texture_from_line(&path, line)?
+                }
+                "map_Ks" => material.specular_map = Self::texture_from_line(&path, line)?,
+                _ => line.unsupported(),
+            }
+            Ok(())
+        })?;
+        Ok(materials)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wireframe::{write_test_file, WireframeModel};
+
+    #[test]
+    fn test_color_from_str() {
+        let color = Material::color_from_str(" 0.1 0.2 0.3").unwrap();
+        assert_eq!(
+            color,
+            Vec3 {
+                x: 0.1,
+                y: 0.2,
+                z: 0.3
+            }
+        );
+        let gray = Material::color_from_str("0.5").unwrap();
+        assert_eq!(
+            gray,
+            Vec3 {
+                x: 0.5,
+                y: 0.5,
+                z: 0.5
+            }
+        );
+        let error = Material::color_from_str("0.1 0.2").unwrap_err();
+        assert_eq!(error.message, "expected blue value");
+    }
+
+    #[test]
+    fn test_materials_per_face() {
+        write_test_file(
+            "materials/scene.mtl",
+            "newmtl red\nKd 1 0 0\nNs 10\nillum 2\n\nnewmtl glass\nKd 0.5\nd 0.25\n",
+        );
+        let path = write_test_file(
+            "materials/scene.obj",
+            "mtllib scene.mtl\nv 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+             f 1 2 3\nusemtl glass\nf 1 2 3 4\nusemtl red\nf 3 4 1\n",
+        );
+        let model = WireframeModel::from_file(path).unwrap();
+        assert_eq!(model.materials.len(), 2);
+        assert_eq!(model.materials[0].name, "red");
+        assert_eq!(model.materials[0].shininess, 10.0);
+        assert_eq!(model.materials[1].dissolve, 0.25);
+        assert_eq!(model.face_materials, [None, Some(1), Some(1), Some(0)]);
+    }
+
+    #[test]
+    fn test_missing_materials() {
+        // unknown names and files which can't be loaded fall back to the default material
+        let path = write_test_file(
+            "missing_materials/scene.obj",
+            "mtllib missing.mtl\nv 0 0 0\nv 1 0 0\nv 1 1 0\nusemtl  metal\nf 1 2 3\n",
+        );
+        let model = WireframeModel::from_file(path).unwrap();
+        assert!(model.materials.is_empty());
+        assert_eq!(model.face_materials, [None]);
+
+        write_test_file(
+            "missing_materials/textures.mtl",
+            "newmtl wood\nKd 0.5\nmap_Kd missing.png\n",
+        );
+        let path = write_test_file(
+            "missing_materials/textures.obj",
+            "mtllib textures.mtl\nv 0 0 0\nv 1 0 0\nv 1 1 0\nusemtl wood\nf 1 2 3\n",
+        );
+        let model = WireframeModel::from_file(path).unwrap();
+        assert_eq!(
+            model.materials[0].diffuse,
+            Vec3 {
+                x: 0.5,
+                y: 0.5,
+                z: 0.5
+            }
+        );
+        assert!(model.materials[0].diffuse_map.is_none());
+        assert_eq!(model.face_materials, [Some(0)]);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::point::{cross, diff, Vec3};
+use crate::point::{cross, diff, Vec3, Vec4};
 use std::ops::Mul;
 
 pub(crate) struct ViewPort {
@@ -39,13 +39,37 @@ impl Matrix {
         self.m.len()
     }
 
-    pub(crate) fn to_vector(&self) -> Vec3<f32> {
-        assert_eq!(self.cols(), 1);
-        assert_eq!(self.rows(), 4);
+    /// Multiplies 4x4 matrix by the point `v` (with `w` = 1) without the perspective divide.
+    pub(crate) fn homogeneous(&self, v: Vec3<f32>) -> Vec4<f32> {
+        self.transform_vec4(Vec4 {
+            x: v.x,
+            y: v.y,
+            z: v.z,
+            w: 1.0,
+        })
+    }
+
+    /// Multiplies 4x4 matrix by the homogeneous point `v`.
+    pub(crate) fn transform_vec4(&self, v: Vec4<f32>) -> Vec4<f32> {
+        let row = |i: usize| {
+            let m = &self.m[i];
+            m[0] * v.x + m[1] * v.y + m[2] * v.z + m[3] * v.w
+        };
+        Vec4 {
+            x: row(0),
+            y: row(1),
+            z: row(2),
+            w: row(3),
+        }
+    }
+
+    /// Same as `homogeneous` divided by the resulting `w`.
+    pub(crate) fn transform(&self, v: Vec3<f32>) -> Vec3<f32> {
+        let h = self.homogeneous(v);
         Vec3 {
-            x: self.m[0][0] / self.m[3][0],
-            y: self.m[1][0] / self.m[3][0],
-            z: self.m[2][0] / self.m[3][0],
+            x: h.x / h.w,
+            y: h.y / h.w,
+            z: h.z / h.w,
         }
     }
 }
@@ -66,17 +90,6 @@ impl ViewPort {
     }
 }
 
-impl Vec3<f32> {
-    pub(crate) fn to_matrix(&self) -> Matrix {
-        let mut m = Matrix::new(4, 1);
-        m.m[0][0] = self.x;
-        m.m[1][0] = self.y;
-        m.m[2][0] = self.z;
-        m.m[3][0] = 1.0;
-        return m;
-    }
-}
-
 impl Mul for Matrix {
     type Output = Self;
     fn mul(self, rhs: Self) -> Self::Output {
@@ -0,0 +1,344 @@
+use crate::point::{cross, diff, dot_product, Vec3};
+use crate::wireframe::{Face, Tangent, WireframeModel};
+use std::collections::HashMap;
+
+// Angle weighted vertex normals
+// http://www.bytehazard.com/articles/vertnorm.html
+
+fn angle(a: Vec3<f32>, b: Vec3<f32>) -> f32 {
+    let cos = dot_product(a.normalized(), b.normalized());
+    if cos.is_nan() {
+        0.0
+    } else {
+        cos.clamp(-1.0, 1.0).acos()
+    }
+}
+
+impl WireframeModel {
+    // Unit normals of `faces`, degenerate faces have zero normals
+    fn unit_face_normals(&self) -> Vec<Vec3<f32>> {
+        self.faces
+            .iter()
+            .map(|face| {
+                let p = face.map(|f| self.vertexes[f.vertex_index]);
+                let n = cross(diff(p[1], p[0]), diff(p[2], p[0]));
+                if n == Vec3::default() {
+                    n
+                } else {
+                    n.normalized()
+                }
+            })
+            .collect()
+    }
+
+    fn corner_angles(&self) -> Vec<[f32; 3]> {
+        self.faces
+            .iter()
+            .map(|face| {
+                let p = face.map(|f| self.vertexes[f.vertex_index]);
+                [0, 1, 2].map(|i| angle(diff(p[(i + 1) % 3], p[i]), diff(p[(i + 2) % 3], p[i])))
+            })
+            .collect()
+    }
+
+    /// Replaces `norm` with smooth vertex normals, every face corner is averaged with
+    /// corners of adjacent faces weighted by their angles.
+    /// Faces are not smoothed together if the angle between them is greater than
+    /// `crease_angle` (in radians) or they are in different `face_smoothing_groups`.
+    pub fn compute_normals(&mut self, crease_angle: f32) {
+        let face_normals = self.unit_face_normals();
+        let corner_angles = self.corner_angles();
+        let mut vertex_faces: Vec<Vec<(usize, usize)>> = vec![vec![]; self.vertexes.len()];
+        for (i, face) in self.faces.iter().enumerate() {
+            for (corner, f) in face.iter().enumerate() {
+                vertex_faces[f.vertex_index].push((i, corner));
+            }
+        }
+
+        // group 0 is `s off`, such faces are flat
+        let smoothing = |i: usize| self.face_smoothing_groups.get(i).copied().unwrap_or(1);
+        let min_cos = crease_angle.cos();
+        let mut norm: Vec<Vec3<f32>> = vec![];
+        let mut indices: HashMap<[u32; 3], usize> = HashMap::new();
+        let mut faces = self.faces.clone();
+        for (i, face) in faces.iter_mut().enumerate() {
+            for f in face.iter_mut() {
+                let mut n = Vec3::default();
+                for &(j, corner) in &vertex_faces[f.vertex_index] {
+                    let smooth = smoothing(i) != 0 && smoothing(i) == smoothing(j);
+                    if j == i
+                        || (smooth && dot_product(face_normals[i], face_normals[j]) >= min_cos)
+                    {
+                        let weight = corner_angles[j][corner];
+                        n.x += face_normals[j].x * weight;
+                        n.y += face_normals[j].y * weight;
+                        n.z += face_normals[j].z * weight;
+                    }
+                }
+                let n = if n == Vec3::default() {
+                    face_normals[i]
+                } else {
+                    n.normalized()
+                };
+                let key = [n.x.to_bits(), n.y.to_bits(), n.z.to_bits()];
+                f.norm_index = Some(*indices.entry(key).or_insert_with(|| {
+                    norm.push(n);
+                    norm.len() - 1
+                }));
+            }
+        }
+
+        // triangles of a polygon follow each other and share its corners
+        let mut first_face = 0;
+        for polygon in &mut self.polygons {
+            let contains = |face: &[Face; 3]| {
+                face.iter().all(|f| {
+                    polygon.iter().any(|p| {
+                        p.vertex_index == f.vertex_index && p.texture_index == f.texture_index
+                    })
+                })
+            };
+            let count = self.faces[first_face..]
+                .iter()
+                .take(polygon.len().saturating_sub(2))
+                .take_while(|face| contains(face))
+                .count();
+            for p in polygon.iter_mut() {
+                let corner = faces[first_face..first_face + count]
+                    .iter()
+                    .flatten()
+                    .find(|f| f.vertex_index == p.vertex_index);
+                p.norm_index = corner.and_then(|f| f.norm_index);
+            }
+            first_face += count;
+        }
+        self.faces = faces;
+        self.norm = norm;
+    }
+
+    /// Fills `face_tangents` from texture coordinates, corners sharing the same position,
+    /// texture coordinate and normal get the same tangent.
+    /// Tangents are orthogonal to vertex normals, or face normals if there are none.
+    ///
+    /// Like MikkTSpace, face tangents are weighted by corner angles and mirrored texture
+    /// parts are kept apart. Unlike it, corners are matched by indices instead of values,
+    /// face tangents are summed unnormalized and orthogonalized once per corner, and
+    /// degenerate faces are skipped, so results can differ from baked normal maps.
+    pub fn compute_tangents(&mut self) {
+        let face_normals = self.unit_face_normals();
+        let corner_angles = self.corner_angles();
+        let uv = |f: &Face| f.texture_index.map(|i| self.texture_coord[i]);
+        // tangent and bitangent of every face following `u` and `v` directions
+        let face_tangents: Vec<(Vec3<f32>, Vec3<f32>)> = self
+            .faces
+            .iter()
+            .map(|face| {
+                let p = face.map(|f| self.vertexes[f.vertex_index]);
+                let (t0, t1, t2) = match face.map(|f| uv(&f)) {
+                    [Some(t0), Some(t1), Some(t2)] => (t0, t1, t2),
+                    _ => return (Vec3::default(), Vec3::default()),
+                };
+                let (e1, e2) = (diff(p[1], p[0]), diff(p[2], p[0]));
+                let (du1, dv1) = (t1.0 - t0.0, t1.1 - t0.1);
+                let (du2, dv2) = (t2.0 - t0.0, t2.1 - t0.1);
+                let det = du1 * dv2 - du2 * dv1;
+                if det == 0.0 {
+                    return (Vec3::default(), Vec3::default());
+                }
+                let combine = |a: f32, b: f32| Vec3 {
+                    x: (e1.x * a + e2.x * b) / det,
+                    y: (e1.y * a + e2.y * b) / det,
+                    z: (e1.z * a + e2.z * b) / det,
+                };
+                (combine(dv2, -dv1), combine(-du2, du1))
+            })
+            .collect();
+
+        // mirrored texture parts are accumulated separately, like in MikkTSpace
+        type Key = (usize, Option<usize>, Option<usize>, bool);
+        let key = |i: usize, f: &Face| -> Key {
+            let (t, b) = face_tangents[i];
+            let n = face_normals[i];
+            let mirrored = dot_product(cross(n, t), b) < 0.0;
+            (f.vertex_index, f.texture_index, f.norm_index, mirrored)
+        };
+        let mut sums: HashMap<Key, (Vec3<f32>, Vec3<f32>)> = HashMap::new();
+        for (i, face) in self.faces.iter().enumerate() {
+            let (t, b) = face_tangents[i];
+            for (corner, f) in face.iter().enumerate() {
+                let weight = corner_angles[i][corner];
+                let sum = sums.entry(key(i, f)).or_default();
+                sum.0.x += t.x * weight;
+                sum.0.y += t.y * weight;
+                sum.0.z += t.z * weight;
+                sum.1.x += b.x * weight;
+                sum.1.y += b.y * weight;
+                sum.1.z += b.z * weight;
+            }
+        }
+
+        self.face_tangents = self
+            .faces
+            .iter()
+            .enumerate()
+            .map(|(i, face)| {
+                face.map(|f| {
+                    let n = match f.norm_index {
+                        Some(n) => self.norm[n].normalized(),
+                        None => face_normals[i],
+                    };
+                    let (t, b) = sums[&key(i, &f)];
+                    // Gram-Schmidt, the tangent becomes perpendicular to the normal
+                    let d = dot_product(n, t);
+                    let t = Vec3 {
+                        x: t.x - n.x * d,
+                        y: t.y - n.y * d,
+                        z: t.z - n.z * d,
+                    };
+                    if t == Vec3::default() {
+                        return Tangent::default();
+                    }
+                    let sign = if dot_product(cross(n, t), b) < 0.0 {
+                        -1.0
+                    } else {
+                        1.0
+                    };
+                    Tangent {
+                        tangent: t.normalized(),
+                        sign,
+                    }
+                })
+            })
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wireframe::Vertex3;
+
+    fn cube_corner() -> WireframeModel {
+        // two faces of a cube sharing the 1-2 edge
+        let v = |x, y, z| Vertex3 { x, y, z };
+        let face = |indices: [usize; 4]| -> Vec<Face> {
+            indices
+                .iter()
+                .map(|&i| Face {
+                    vertex_index: i,
+                    texture_index: None,
+                    norm_index: None,
+                })
+                .collect()
+        };
+        let polygons = vec![face([0, 1, 2, 3]), face([1, 4, 5, 2])];
+        let faces = polygons
+            .iter()
+            .flat_map(|p| [[p[0], p[1], p[2]], [p[0], p[2], p[3]]])
+            .collect();
+        WireframeModel {
+            vertexes: vec![
+                v(0.0, 0.0, 0.0),
+                v(1.0, 0.0, 0.0),
+                v(1.0, 1.0, 0.0),
+                v(0.0, 1.0, 0.0),
+                v(1.0, 0.0, -1.0),
+                v(1.0, 1.0, -1.0),
+            ],
+            faces,
+            polygons,
+            face_materials: vec![None; 4],
+            ..WireframeModel::default()
+        }
+    }
+
+    #[test]
+    fn test_smooth_normals() {
+        let mut model = cube_corner();
+        model.compute_normals(std::f32::consts::PI);
+        let n = model.norm[model.faces[0][1].norm_index.unwrap()];
+        let expected = std::f32::consts::FRAC_1_SQRT_2;
+        assert!((n.x - expected).abs() < 1e-6 && n.y.abs() < 1e-6 && (n.z - expected).abs() < 1e-6);
+        // shared corners get the same normal
+        assert_eq!(model.faces[0][1].norm_index, model.faces[2][0].norm_index);
+        assert_eq!(
+            model.polygons[1][0].norm_index,
+            model.faces[2][0].norm_index
+        );
+        assert_eq!(model.norm.len(), 3);
+    }
+
+    #[test]
+    fn test_crease_angle_and_smoothing_groups() {
+        let mut model = cube_corner();
+        model.compute_normals(std::f32::consts::FRAC_PI_4);
+        assert_eq!(model.norm.len(), 2);
+        assert_eq!(
+            model.norm[0],
+            Vec3 {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0
+            }
+        );
+        assert_eq!(model.polygons[1][3].norm_index, Some(1));
+
+        let mut model = cube_corner();
+        model.face_smoothing_groups = vec![1, 1, 2, 2];
+        model.compute_normals(std::f32::consts::PI);
+        assert_eq!(model.norm.len(), 2);
+    }
+
+    #[test]
+    fn test_tangents() {
+        let mut model = cube_corner();
+        // the first quad is mapped as is, the second one is mirrored along `u`
+        model.texture_coord = vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        for (i, polygon) in model.polygons.iter_mut().enumerate() {
+            let order = if i == 0 { [0, 1, 2, 3] } else { [1, 0, 3, 2] };
+            for (f, t) in polygon.iter_mut().zip(order) {
+                f.texture_index = Some(t);
+            }
+        }
+        model.faces = model
+            .polygons
+            .iter()
+            .flat_map(|p| [[p[0], p[1], p[2]], [p[0], p[2], p[3]]])
+            .collect();
+        model.compute_tangents();
+
+        let x = Vec3 {
+            x: 1.0,
+            y: 0.0,
+            z: 0.0,
+        };
+        let y = Vec3 {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        };
+        let z = Vec3 {
+            x: 0.0,
+            y: 0.0,
+            z: 1.0,
+        };
+        let t = model.face_tangents[0][0];
+        assert_eq!(
+            t,
+            Tangent {
+                tangent: x,
+                sign: 1.0
+            }
+        );
+        assert_eq!(t.bitangent(z), y);
+        let t = model.face_tangents[2][0];
+        assert_eq!(
+            t,
+            Tangent {
+                tangent: z,
+                sign: -1.0
+            }
+        );
+        assert_eq!(t.bitangent(x), y);
+    }
+}
@@ -0,0 +1,623 @@
+use crate::loader::MeshError;
+use crate::point::Vec3;
+use crate::polygon::triangulate;
+use crate::wireframe::{Face, Group, Vertex3, WireframeModel};
+use std::collections::HashMap;
+use std::fs;
+
+// Polygon file format, ascii and binary little endian variants
+// http://paulbourke.net/dataformats/ply/
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Scalar {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl Scalar {
+    fn from_str(s: &str) -> Option<Scalar> {
+        match s {
+            "char" | "int8" => Some(Scalar::Int8),
+            "uchar" | "uint8" => Some(Scalar::UInt8),
+            "short" | "int16" => Some(Scalar::Int16),
+            "ushort" | "uint16" => Some(Scalar::UInt16),
+            "int" | "int32" => Some(Scalar::Int32),
+            "uint" | "uint32" => Some(Scalar::UInt32),
+            "float" | "float32" => Some(Scalar::Float32),
+            "double" | "float64" => Some(Scalar::Float64),
+            _ => None,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::Int8 | Scalar::UInt8 => 1,
+            Scalar::Int16 | Scalar::UInt16 => 2,
+            Scalar::Int32 | Scalar::UInt32 | Scalar::Float32 => 4,
+            Scalar::Float64 => 8,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum PropertyType {
+    Scalar(Scalar),
+    /// Type of the length and type of items
+    List(Scalar, Scalar),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct Property {
+    name: String,
+    kind: PropertyType,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    fn position(&self, names: &[&str]) -> Option<usize> {
+        self.properties
+            .iter()
+            .position(|p| names.contains(&p.name.as_str()))
+    }
+}
+
+struct Header {
+    binary: bool,
+    elements: Vec<Element>,
+    /// Offset of the body in bytes
+    length: usize,
+    /// Number of header lines
+    lines: usize,
+}
+
+fn parse_header(data: &[u8]) -> Result<Header, String> {
+    let mut binary = None;
+    let mut elements: Vec<Element> = vec![];
+    let mut length = 0;
+    let mut lines = 0;
+    loop {
+        let end = data[length..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| "expected end_header".to_string())?;
+        let line = String::from_utf8_lossy(&data[length..length + end]);
+        length += end + 1;
+        lines += 1;
+        let error = |message: String| format!("line {}: {}", lines, message);
+        let tokens: Vec<&str> = line.split_ascii_whitespace().collect();
+        match tokens[..] {
+            ["ply"] if lines == 1 => {}
+            _ if lines == 1 => return Err(error("expected ply".to_string())),
+            ["format", "ascii", _] => binary = Some(false),
+            ["format", "binary_little_endian", _] => binary = Some(true),
+            ["format", format, ..] => {
+                return Err(error(format!("unsupported format `{}`", format)));
+            }
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            ["element", name, count] => {
+                let count = count
+                    .parse::<usize>()
+                    .map_err(|_| error(format!("expected element count, found `{}`", count)))?;
+                elements.push(Element {
+                    name: name.to_string(),
+                    count,
+                    properties: vec![],
+                });
+            }
+            ["property", ..] => {
+                let kind = match tokens[1..] {
+                    ["list", count, item, _] => Scalar::from_str(count)
+                        .zip(Scalar::from_str(item))
+                        .map(|(count, item)| PropertyType::List(count, item)),
+                    [kind, _] => Scalar::from_str(kind).map(PropertyType::Scalar),
+                    _ => None,
+                }
+                .ok_or_else(|| error(format!("invalid property `{}`", line)))?;
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| error("expected element before property".to_string()))?;
+                element.properties.push(Property {
+                    name: tokens[tokens.len() - 1].to_string(),
+                    kind,
+                });
+            }
+            ["end_header"] => break,
+            _ => return Err(error(format!("unexpected `{}`", line.trim()))),
+        }
+    }
+    Ok(Header {
+        binary: binary.ok_or_else(|| "expected format".to_string())?,
+        elements,
+        length,
+        lines,
+    })
+}
+
+// Ascii elements are one per line, binary ones are packed one after another
+enum Reader<'a> {
+    Ascii {
+        lines: std::iter::Enumerate<std::str::Lines<'a>>,
+        header_lines: usize,
+        /// Current line number counting from the file start
+        line: usize,
+        tokens: std::str::SplitAsciiWhitespace<'a>,
+    },
+    Binary {
+        data: &'a [u8],
+        position: usize,
+    },
+}
+
+impl Reader<'_> {
+    fn start_element(&mut self) -> Result<(), String> {
+        if let Reader::Ascii {
+            lines,
+            header_lines,
+            line,
+            tokens,
+        } = self
+        {
+            for (n, text) in lines.by_ref() {
+                if !text.trim().is_empty() {
+                    *line = *header_lines + n + 1;
+                    *tokens = text.split_ascii_whitespace();
+                    return Ok(());
+                }
+            }
+            return Err("unexpected end of file".to_string());
+        }
+        Ok(())
+    }
+
+    fn scalar(&mut self, scalar: Scalar) -> Result<f64, String> {
+        match self {
+            Reader::Ascii { line, tokens, .. } => {
+                let token = tokens
+                    .next()
+                    .ok_or_else(|| format!("line {}: expected value", *line))?;
+                token
+                    .parse::<f64>()
+                    .map_err(|_| format!("line {}: expected number, found `{}`", *line, token))
+            }
+            Reader::Binary { data, position } => {
+                let bytes = data
+                    .get(*position..*position + scalar.size())
+                    .ok_or_else(|| "unexpected end of file".to_string())?;
+                *position += scalar.size();
+                let mut b = [0; 8];
+                b[..bytes.len()].copy_from_slice(bytes);
+                Ok(match scalar {
+                    Scalar::Int8 => b[0] as i8 as f64,
+                    Scalar::UInt8 => b[0] as f64,
+                    Scalar::Int16 => i16::from_le_bytes([b[0], b[1]]) as f64,
+                    Scalar::UInt16 => u16::from_le_bytes([b[0], b[1]]) as f64,
+                    Scalar::Int32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    Scalar::UInt32 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    Scalar::Float32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    Scalar::Float64 => f64::from_le_bytes(b),
+                })
+            }
+        }
+    }
+
+    fn property(&mut self, kind: &PropertyType) -> Result<Vec<f64>, String> {
+        match *kind {
+            PropertyType::Scalar(scalar) => Ok(vec![self.scalar(scalar)?]),
+            PropertyType::List(count, item) => {
+                let count = self.scalar(count)? as usize;
+                (0..count).map(|_| self.scalar(item)).collect()
+            }
+        }
+    }
+}
+
+fn parse(data: &[u8]) -> Result<WireframeModel, String> {
+    let header = parse_header(data)?;
+    let body = &data[header.length..];
+    let mut reader = if header.binary {
+        Reader::Binary {
+            data: body,
+            position: 0,
+        }
+    } else {
+        let text = std::str::from_utf8(body).map_err(|_| "invalid utf8".to_string())?;
+        Reader::Ascii {
+            lines: text.lines().enumerate(),
+            header_lines: header.lines,
+            line: 0,
+            tokens: "".split_ascii_whitespace(),
+        }
+    };
+
+    let mut model = WireframeModel::default();
+    let mut polygons: Vec<Vec<usize>> = vec![];
+    let mut has_norm = false;
+    let mut has_uv = false;
+    for element in &header.elements {
+        let index = |names: &[&str]| element.position(names);
+        let xyz = [index(&["x"]), index(&["y"]), index(&["z"])];
+        let normal = [index(&["nx"]), index(&["ny"]), index(&["nz"])];
+        let uv = [
+            index(&["u", "s", "texture_u"]),
+            index(&["v", "t", "texture_v"]),
+        ];
+        let rgb = [
+            index(&["red", "r", "diffuse_red"]),
+            index(&["green", "g", "diffuse_green"]),
+            index(&["blue", "b", "diffuse_blue"]),
+        ];
+        let indices = index(&["vertex_indices", "vertex_index"]);
+        // vertex attributes take a single value, lists may even be empty
+        for &i in xyz.iter().chain(&normal).chain(&uv).chain(&rgb).flatten() {
+            let property = &element.properties[i];
+            if let PropertyType::List(..) = property.kind {
+                return Err(format!(
+                    "property `{}` of `{}` should be a scalar, found a list",
+                    property.name, element.name
+                ));
+            }
+        }
+        // 8 bit colors are in 0..255 range, floating point ones are already normalized
+        let color_scale: Vec<f64> = rgb
+            .iter()
+            .map(|i| match i.map(|i| &element.properties[i].kind) {
+                Some(PropertyType::Scalar(Scalar::Float32 | Scalar::Float64)) => 1.0,
+                Some(PropertyType::Scalar(Scalar::UInt16)) => 65535.0,
+                _ => 255.0,
+            })
+            .collect();
+        let is_vertex = element.name == "vertex";
+        let is_face = element.name == "face";
+        has_norm |= is_vertex && normal.iter().all(|i| i.is_some());
+        has_uv |= is_vertex && uv.iter().all(|i| i.is_some());
+
+        for _ in 0..element.count {
+            reader.start_element()?;
+            let values = element
+                .properties
+                .iter()
+                .map(|p| reader.property(&p.kind))
+                .collect::<Result<Vec<Vec<f64>>, String>>()?;
+            let scalar = |i: Option<usize>| i.map(|i| values[i][0] as f32);
+            if is_vertex {
+                let [x, y, z] = xyz.map(scalar);
+                model.vertexes.push(Vertex3 {
+                    x: x.unwrap_or_default(),
+                    y: y.unwrap_or_default(),
+                    z: z.unwrap_or_default(),
+                });
+                if let [Some(x), Some(y), Some(z)] = normal.map(scalar) {
+                    model.norm.push(Vec3 { x, y, z });
+                }
+                if let [Some(u), Some(v)] = uv.map(scalar) {
+                    model.texture_coord.push((u, v));
+                }
+                if let [Some(r), Some(g), Some(b)] = rgb.map(scalar) {
+                    model.vertex_colors.push(Vec3 {
+                        x: r / color_scale[0] as f32,
+                        y: g / color_scale[1] as f32,
+                        z: b / color_scale[2] as f32,
+                    });
+                }
+            } else if let (true, Some(i)) = (is_face, indices) {
+                polygons.push(values[i].iter().map(|&i| i as usize).collect());
+            }
+        }
+    }
+
+    let count = model.vertexes.len();
+    for polygon in polygons {
+        if let Some(i) = polygon.iter().find(|&&i| i >= count) {
+            return Err(format!(
+                "face index {} out of range, only {} vertices",
+                i, count
+            ));
+        }
+        let polygon: Vec<Face> = polygon
+            .iter()
+            .map(|&i| Face {
+                vertex_index: i,
+                texture_index: has_uv.then_some(i),
+                norm_index: has_norm.then_some(i),
+            })
+            .collect();
+        let points: Vec<Vertex3> = polygon
+            .iter()
+            .map(|f| model.vertexes[f.vertex_index])
+            .collect();
+        for t in triangulate(&points) {
+            model.faces.push(t.map(|i| polygon[i]));
+            model.face_materials.push(None);
+        }
+        model.polygons.push(polygon);
+    }
+    let mut group = Group::new("default".to_string(), 0, 0);
+    group.end(model.faces.len(), model.polygons.len());
+    model.groups.push(group);
+    Ok(model)
+}
+
+impl WireframeModel {
+    /// Loads ascii or binary little endian PLY file with optional normals,
+    /// texture coordinates and vertex colors.
+    pub fn from_ply(path: String) -> Result<WireframeModel, MeshError> {
+        let data = fs::read(&path).map_err(|error| MeshError::Io {
+            path: path.clone(),
+            error,
+        })?;
+        parse(&data).map_err(|message| MeshError::Format { path, message })
+    }
+
+    /// Writes binary little endian PLY file with polygons, normals, texture coordinates
+    /// and vertex colors. PLY attributes are per vertex, so vertices are duplicated
+    /// when faces use different normals or texture coordinates with the same position.
+    pub fn write_ply(&self, path: String) -> Result<(), MeshError> {
+        let corners = || self.polygons.iter().flatten();
+        let has_norm = !self.norm.is_empty() && corners().all(|f| f.norm_index.is_some());
+        let has_uv = !self.texture_coord.is_empty() && corners().all(|f| f.texture_index.is_some());
+        let has_color =
+            self.vertex_colors.len() == self.vertexes.len() && !self.vertexes.is_empty();
+        let attributes = |f: &Face| {
+            (
+                f.texture_index.filter(|_| has_uv),
+                f.norm_index.filter(|_| has_norm),
+            )
+        };
+
+        // the first use of a position keeps its index, other combinations are appended
+        let mut vertexes: Vec<(usize, Option<usize>, Option<usize>)> =
+            (0..self.vertexes.len()).map(|i| (i, None, None)).collect();
+        let mut used = vec![false; self.vertexes.len()];
+        let mut extra: HashMap<(usize, Option<usize>, Option<usize>), usize> = HashMap::new();
+        let mut polygons: Vec<Vec<usize>> = vec![];
+        for polygon in &self.polygons {
+            if polygon.len() > u8::MAX as usize {
+                return Err(MeshError::Format {
+                    path,
+                    message: format!("polygon with {} vertices is too big", polygon.len()),
+                });
+            }
+            let indices = polygon.iter().map(|f| {
+                let (vt, vn) = attributes(f);
+                let key = (f.vertex_index, vt, vn);
+                if !used[f.vertex_index] {
+                    used[f.vertex_index] = true;
+                    vertexes[f.vertex_index] = key;
+                    f.vertex_index
+                } else if vertexes[f.vertex_index] == key {
+                    f.vertex_index
+                } else {
+                    *extra.entry(key).or_insert_with(|| {
+                        vertexes.push(key);
+                        vertexes.len() - 1
+                    })
+                }
+            });
+            polygons.push(indices.collect());
+        }
+
+        let mut header = format!(
+            "ply\nformat binary_little_endian 1.0\nelement vertex {}\n\
+             property float x\nproperty float y\nproperty float z\n",
+            vertexes.len()
+        );
+        if has_norm {
+            header += "property float nx\nproperty float ny\nproperty float nz\n";
+        }
+        if has_uv {
+            header += "property float u\nproperty float v\n";
+        }
+        if has_color {
+            header += "property uchar red\nproperty uchar green\nproperty uchar blue\n";
+        }
+        header += &format!(
+            "element face {}\nproperty list uchar int vertex_indices\nend_header\n",
+            polygons.len()
+        );
+
+        let mut data = header.into_bytes();
+        let floats = |data: &mut Vec<u8>, values: &[f32]| {
+            for v in values {
+                data.extend_from_slice(&v.to_le_bytes());
+            }
+        };
+        // unused positions of point clouds keep normals stored per vertex
+        let per_vertex_norm = self.norm.len() == self.vertexes.len();
+        for &(i, vt, vn) in &vertexes {
+            let p = self.vertexes[i];
+            floats(&mut data, &[p.x, p.y, p.z]);
+            if has_norm {
+                let n = match vn {
+                    Some(vn) => self.norm[vn],
+                    None if per_vertex_norm => self.norm[i],
+                    None => Vec3::default(),
+                };
+                floats(&mut data, &[n.x, n.y, n.z]);
+            }
+            if has_uv {
+                let (u, v) = vt.map(|vt| self.texture_coord[vt]).unwrap_or_default();
+                floats(&mut data, &[u, v]);
+            }
+            if has_color {
+                let c = self.vertex_colors[i];
+                for channel in [c.x, c.y, c.z] {
+                    data.push((channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+                }
+            }
+        }
+        for polygon in &polygons {
+            data.push(polygon.len() as u8);
+            for &i in polygon {
+                data.extend_from_slice(&(i as i32).to_le_bytes());
+            }
+        }
+        fs::write(&path, data).map_err(|error| MeshError::Io { path, error })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wireframe::test_path;
+
+    #[test]
+    fn test_ascii() {
+        let model = parse(
+            b"ply\nformat ascii 1.0\ncomment made by hand\n\
+              element vertex 4\nproperty float x\nproperty float y\nproperty float z\n\
+              property uchar red\nproperty uchar green\nproperty uchar blue\n\
+              element face 1\nproperty list uchar int vertex_indices\nend_header\n\
+              0 0 0 255 0 0\n1 0 0 0 255 0\n1 1 0 0 0 255\n0 1 0 255 255 255\n4 0 1 2 3\n",
+        )
+        .unwrap();
+        assert_eq!(model.vertexes.len(), 4);
+        assert_eq!(model.polygons.len(), 1);
+        assert_eq!(model.faces.len(), 2);
+        assert_eq!(model.faces[1][2].vertex_index, 3);
+        assert_eq!(model.faces[0][0].norm_index, None);
+        assert_eq!(
+            model.vertex_colors[2],
+            Vec3 {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0
+            }
+        );
+        assert_eq!(model.groups[0].face_indices().collect::<Vec<_>>(), [0, 1]);
+
+        let error =
+            parse(b"ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nend_header\nabc\n")
+                .err()
+                .unwrap();
+        assert_eq!(error, "line 6: expected number, found `abc`");
+        let error = parse(b"ply\nformat binary_big_endian 1.0\nend_header\n")
+            .err()
+            .unwrap();
+        assert_eq!(error, "line 2: unsupported format `binary_big_endian`");
+        let error = parse(
+            b"ply\nformat ascii 1.0\nelement vertex 1\n\
+              property list uchar float x\nend_header\n0\n",
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            error,
+            "property `x` of `vertex` should be a scalar, found a list"
+        );
+    }
+
+    #[test]
+    fn test_binary() {
+        let mut data = b"ply\nformat binary_little_endian 1.0\n\
+                         element vertex 3\nproperty float x\nproperty float y\nproperty float z\n\
+                         property float nx\nproperty float ny\nproperty float nz\n\
+                         element edge 1\nproperty int vertex1\nproperty int vertex2\n\
+                         element face 1\nproperty list uchar uint vertex_indices\nend_header\n"
+            .to_vec();
+        for v in [[0.0f32, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]] {
+            for c in v.iter().chain(&[0.0, 0.0, 1.0]) {
+                data.extend_from_slice(&c.to_le_bytes());
+            }
+        }
+        data.extend_from_slice(&0i32.to_le_bytes());
+        data.extend_from_slice(&1i32.to_le_bytes());
+        data.push(3);
+        for i in [0u32, 1, 2] {
+            data.extend_from_slice(&i.to_le_bytes());
+        }
+
+        let model = parse(&data).unwrap();
+        assert_eq!(
+            model.vertexes[2],
+            Vec3 {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0
+            }
+        );
+        assert_eq!(model.norm.len(), 3);
+        assert_eq!(model.faces.len(), 1);
+        assert_eq!(model.faces[0][1].norm_index, Some(1));
+        assert!(model.vertex_colors.is_empty());
+    }
+
+    #[test]
+    fn test_write_ply() {
+        let model = parse(
+            b"ply\nformat ascii 1.0\n\
+              element vertex 5\nproperty float x\nproperty float y\nproperty float z\n\
+              property float nx\nproperty float ny\nproperty float nz\n\
+              property uchar red\nproperty uchar green\nproperty uchar blue\n\
+              element face 2\nproperty list uchar int vertex_indices\nend_header\n\
+              0 0 0 0 0 1 255 0 0\n1 0 0 0 0 1 0 255 0\n1 1 0 0 0 1 0 0 255\n\
+              0 1 0 0 0 1 255 255 255\n5 5 5 0 1 0 0 0 0\n4 0 1 2 3\n3 0 2 3\n",
+        )
+        .unwrap();
+        let path = test_path("write.ply").to_string_lossy().to_string();
+        model.write_ply(path.clone()).unwrap();
+
+        let copy = WireframeModel::from_ply(path).unwrap();
+        assert_eq!(copy.vertexes, model.vertexes);
+        assert_eq!(copy.norm, model.norm);
+        assert_eq!(copy.vertex_colors, model.vertex_colors);
+        assert_eq!(copy.polygons, model.polygons);
+        assert_eq!(copy.faces, model.faces);
+    }
+
+    #[test]
+    fn test_write_ply_splits_vertices() {
+        let polygon = |norms: [usize; 3]| -> Vec<Face> {
+            (0..3)
+                .map(|i| Face {
+                    vertex_index: i,
+                    texture_index: None,
+                    norm_index: Some(norms[i]),
+                })
+                .collect()
+        };
+        let model = WireframeModel {
+            vertexes: vec![Vec3::default(); 3],
+            norm: vec![
+                Vec3 {
+                    x: 0.0,
+                    y: 0.0,
+                    z: 1.0,
+                },
+                Vec3 {
+                    x: 0.0,
+                    y: 1.0,
+                    z: 0.0,
+                },
+            ],
+            polygons: vec![polygon([0, 0, 0]), polygon([0, 1, 1])],
+            ..WireframeModel::default()
+        };
+        let path = test_path("split.ply").to_string_lossy().to_string();
+        model.write_ply(path.clone()).unwrap();
+
+        let copy = WireframeModel::from_ply(path).unwrap();
+        assert_eq!(copy.vertexes.len(), 5);
+        let indices: Vec<usize> = copy.polygons[1].iter().map(|f| f.vertex_index).collect();
+        assert_eq!(indices, [0, 3, 4]);
+        assert_eq!(
+            copy.norm[4],
+            Vec3 {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0
+            }
+        );
+    }
+}
@@ -1,18 +1,27 @@
 use std::ops::{Add, Mul, Sub};
 
-#[derive(Clone, Copy)]
-pub(crate) struct Vec2<T> {
-    pub(crate) x: T,
-    pub(crate) y: T,
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Vec2<T> {
+    pub x: T,
+    pub y: T,
 }
 
 pub(crate) type Point = Vec2<u16>;
 
-#[derive(Clone, Copy, PartialEq, Debug)]
-pub(crate) struct Vec3<T> {
-    pub(crate) x: T,
-    pub(crate) y: T,
-    pub(crate) z: T,
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Vec3<T> {
+    pub x: T,
+    pub y: T,
+    pub z: T,
+}
+
+/// Homogeneous coordinates, the point is `(x/w, y/w, z/w)`.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Vec4<T> {
+    pub x: T,
+    pub y: T,
+    pub z: T,
+    pub w: T,
 }
 
 impl Vec2<u16> {
@@ -36,16 +45,6 @@ impl Vec2<u16> {
     }
 }
 
-impl Vec3<u16> {
-    pub(crate) fn as_i32(self) -> Vec3<i32> {
-        Vec3 {
-            x: self.x as i32,
-            y: self.y as i32,
-            z: self.z as i32,
-        }
-    }
-}
-
 impl Vec3<f32> {
     fn norm(self) -> f32 {
         f32::sqrt(self.x * self.x + self.y * self.y + self.z * self.z)
@@ -66,14 +65,6 @@ impl Vec3<f32> {
             z: self.z / n,
         };
     }
-
-    pub(crate) fn as_u16(self) -> Vec3<u16> {
-        Vec3 {
-            x: self.x as u16,
-            y: self.y as u16,
-            z: self.z as u16,
-        }
-    }
 }
 
 pub(crate) fn barycentric(pts: [Vec2<i32>; 3], p: Vec2<i32>) -> Vec3<f32> {
@@ -103,9 +94,31 @@ pub(crate) fn barycentric(pts: [Vec2<i32>; 3], p: Vec2<i32>) -> Vec3<f32> {
     };
 }
 
-pub(crate) fn barycentric_3d(pts: [Vec3<i32>; 3], p: Vec2<i32>) -> Vec3<f32> {
-    let pts_2d = pts.map(|p| Vec2 { x: p.x, y: p.y });
-    barycentric(pts_2d, p)
+pub(crate) fn barycentric_f32(pts: [Vec2<f32>; 3], p: Vec2<f32>) -> Vec3<f32> {
+    let u = cross(
+        Vec3 {
+            x: pts[2].x - pts[0].x,
+            y: pts[1].x - pts[0].x,
+            z: pts[0].x - p.x,
+        },
+        Vec3 {
+            x: pts[2].y - pts[0].y,
+            y: pts[1].y - pts[0].y,
+            z: pts[0].y - p.y,
+        },
+    );
+    if u.z.abs() < f32::EPSILON {
+        return Vec3 {
+            x: -1.0,
+            y: 1.0,
+            z: 1.0,
+        };
+    }
+    Vec3 {
+        x: 1.0 - (u.x + u.y) / u.z,
+        y: u.y / u.z,
+        z: u.x / u.z,
+    }
 }
 
 pub(crate) fn cross<T: Mul<Output = T> + Sub<Output = T> + Copy>(
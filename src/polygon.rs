@@ -0,0 +1,155 @@
+use crate::point::{cross, diff, Vec2, Vec3};
+
+// Polygon triangulation for faces with more than three vertices
+// https://en.wikipedia.org/wiki/Polygon_triangulation#Ear_clipping_method
+
+/// Splits a (nearly) planar polygon into triangles.
+/// Returns indices of `points` keeping the polygon winding order.
+pub(crate) fn triangulate(points: &[Vec3<f32>]) -> Vec<[usize; 3]> {
+    if points.len() < 3 {
+        return vec![];
+    }
+    let projected = project(points);
+    if is_convex(&projected) {
+        return fan(points.len());
+    }
+    ear_clipping(&projected)
+}
+
+fn fan(n: usize) -> Vec<[usize; 3]> {
+    (1..n - 1).map(|i| [0, i, i + 1]).collect()
+}
+
+// Newell's method, works for concave polygons as well
+fn normal(points: &[Vec3<f32>]) -> Vec3<f32> {
+    let mut n = Vec3::default();
+    for i in 0..points.len() {
+        let a = points[i];
+        let b = points[(i + 1) % points.len()];
+        n.x += (a.y - b.y) * (a.z + b.z);
+        n.y += (a.z - b.z) * (a.x + b.x);
+        n.z += (a.x - b.x) * (a.y + b.y);
+    }
+    n
+}
+
+// Drops the dominant axis of the polygon normal, so the result is counter-clockwise
+fn project(points: &[Vec3<f32>]) -> Vec<Vec2<f32>> {
+    let n = normal(points);
+    let (ax, ay, az) = (n.x.abs(), n.y.abs(), n.z.abs());
+    points
+        .iter()
+        .map(|p| {
+            if az >= ax && az >= ay {
+                Vec2 {
+                    x: p.x,
+                    y: if n.z < 0.0 { -p.y } else { p.y },
+                }
+            } else if ax >= ay {
+                Vec2 {
+                    x: p.y,
+                    y: if n.x < 0.0 { -p.z } else { p.z },
+                }
+            } else {
+                Vec2 {
+                    x: p.z,
+                    y: if n.y < 0.0 { -p.x } else { p.x },
+                }
+            }
+        })
+        .collect()
+}
+
+fn turn(a: Vec2<f32>, b: Vec2<f32>, c: Vec2<f32>) -> f32 {
+    let to_3d = |v: Vec2<f32>| Vec3 {
+        x: v.x,
+        y: v.y,
+        z: 0.0,
+    };
+    cross(diff(to_3d(b), to_3d(a)), diff(to_3d(c), to_3d(b))).z
+}
+
+fn is_convex(points: &[Vec2<f32>]) -> bool {
+    let n = points.len();
+    (0..n).all(|i| turn(points[i], points[(i + 1) % n], points[(i + 2) % n]) >= 0.0)
+}
+
+fn inside(p: Vec2<f32>, t: [Vec2<f32>; 3]) -> bool {
+    turn(t[0], t[1], p) >= 0.0 && turn(t[1], t[2], p) >= 0.0 && turn(t[2], t[0], p) >= 0.0
+}
+
+fn ear_clipping(points: &[Vec2<f32>]) -> Vec<[usize; 3]> {
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = vec![];
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let ear = (0..n).find(|&i| {
+            let t = [
+                remaining[(i + n - 1) % n],
+                remaining[i],
+                remaining[(i + 1) % n],
+            ];
+            let corner = t.map(|j| points[j]);
+            turn(corner[0], corner[1], corner[2]) > 0.0
+                && remaining
+                    .iter()
+                    .filter(|j| !t.contains(j))
+                    .all(|&j| !inside(points[j], corner))
+        });
+        match ear {
+            Some(i) => {
+                triangles.push([
+                    remaining[(i + n - 1) % n],
+                    remaining[i],
+                    remaining[(i + 1) % n],
+                ]);
+                remaining.remove(i);
+            }
+            // self-intersecting or degenerate polygon, there is no good answer
+            None => break,
+        }
+    }
+    triangles.extend(fan(remaining.len()).iter().map(|t| t.map(|i| remaining[i])));
+    triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(coords: &[(f32, f32)]) -> Vec<Vec3<f32>> {
+        coords.iter().map(|&(x, y)| Vec3 { x, y, z: 0.0 }).collect()
+    }
+
+    #[test]
+    fn test_triangulate_quad() {
+        let quad = points(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]);
+        assert_eq!(triangulate(&quad), vec![[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn test_triangulate_concave() {
+        // arrow head, fan from the first vertex would cover the notch at (1, 0.5)
+        let arrow = points(&[(0.0, 0.0), (2.0, 0.0), (1.0, 0.5), (2.0, 1.0), (0.0, 1.0)]);
+        let triangles = triangulate(&arrow);
+        assert_eq!(triangles.len(), 3);
+        for t in &triangles {
+            let p = t.map(|i| project(&arrow)[i]);
+            assert!(turn(p[0], p[1], p[2]) > 0.0);
+        }
+        let area: f32 = triangles
+            .iter()
+            .map(|t| {
+                let p = t.map(|i| arrow[i]);
+                cross(diff(p[1], p[0]), diff(p[2], p[0])).z / 2.0
+            })
+            .sum();
+        assert_eq!(area, 1.5);
+    }
+
+    #[test]
+    fn test_triangulate_clockwise() {
+        let quad = points(&[(0.0, 0.0), (0.0, 1.0), (1.0, 1.0), (1.0, 0.0)]);
+        assert_eq!(triangulate(&quad), vec![[0, 1, 2], [0, 2, 3]]);
+    }
+}
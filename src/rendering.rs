@@ -1,29 +1,81 @@
+use crate::camera::Camera;
+use crate::hdr::HDRImage;
+use crate::light::{Light, Phong, Shadow};
+use crate::line::LineStyle;
+use crate::material::Material;
 use crate::matrix::{look_at, Matrix, ViewPort};
-use crate::point::{cross, diff, dot_product, Point, Vec2, Vec3};
-use crate::rgb_image::{RGBColor, RGBImage, BLACK_COLOR};
+use crate::point::{cross, diff, dot_product, Point, Vec2, Vec3, Vec4};
+use crate::rgb_image::{RGBColor, RGBImage, BLACK_COLOR, WHITE_COLOR};
+use crate::rgba_image::{Blending, RGBAColor, RGBAImage};
+use crate::shader::{DepthBuffer, Shader};
+use crate::texture::{uv_derivatives, Texture};
 use crate::wireframe::{Face, WireframeModel};
-use image::{DynamicImage, GenericImageView};
+use std::collections::HashSet;
 
 impl RGBImage {
-    pub(crate) fn render_frame(&mut self, wireframe: WireframeModel, color: RGBColor) {
-        for face in wireframe.faces {
-            for j in 0..3 {
-                fn normalize(value: f32, side: u16) -> u16 {
-                    ((value + 1.0) * (side as f32 - 1.0) / 2.0) as u16
+    pub(crate) fn render_frame(
+        &mut self,
+        wireframe: WireframeModel,
+        color: RGBColor,
+        style: LineStyle,
+    ) {
+        self.render_frame_colored(&wireframe, &|_| color, style);
+    }
+
+    // Every group has its own color
+    pub fn render_frame_groups(&mut self, wireframe: WireframeModel, style: LineStyle) {
+        let polygon_colors = wireframe.polygon_colors();
+        self.render_frame_colored(&wireframe, &|polygon| polygon_colors[polygon], style);
+    }
+
+    fn render_frame_colored(
+        &mut self,
+        wireframe: &WireframeModel,
+        polygon_color: &dyn Fn(usize) -> RGBColor,
+        style: LineStyle,
+    ) {
+        let (width, height) = (self.width as f32, self.height as f32);
+        // the model is already in the [-1, 1] cube, `z` is dropped
+        let screen = |v: Vec3<f32>| Vec2 {
+            x: (v.x + 1.0) * (width - 1.0) / 2.0,
+            y: (v.y + 1.0) * (height - 1.0) / 2.0,
+        };
+        // edges shared by polygons are drawn once, with the color of the first polygon
+        let mut drawn: HashSet<(usize, usize)> = HashSet::new();
+        let mut edges: Vec<(Vec2<f32>, Vec2<f32>, RGBColor)> = vec![];
+        for i in wireframe.visible_polygons() {
+            let polygon = &wireframe.polygons[i];
+            let color = polygon_color(i);
+            for j in 0..polygon.len() {
+                let (a, b) = (
+                    polygon[j].vertex_index,
+                    polygon[(j + 1) % polygon.len()].vertex_index,
+                );
+                if drawn.insert((a.min(b), a.max(b))) {
+                    let p0 = screen(wireframe.vertexes[a]);
+                    let p1 = screen(wireframe.vertexes[b]);
+                    edges.push((p0, p1, color));
+                }
+            }
+        }
+        match style {
+            LineStyle::Aliased => {
+                for (p0, p1, color) in edges {
+                    self.line_clipped(p0, p1, color);
+                }
+            }
+            LineStyle::AntiAliased => {
+                for (p0, p1, color) in edges {
+                    self.line_aa(p0, p1, color);
                 }
-                let v0 = wireframe.vertexes[face[j].vertex_index];
-                let v1 = wireframe.vertexes[face[(j + 1) % 3].vertex_index];
-                let x0 = normalize(v0.x, self.width);
-                let y0 = normalize(v0.y, self.height);
-                let x1 = normalize(v1.x, self.width);
-                let y1 = normalize(v1.y, self.height);
-                self.line(Point { x: x0, y: y0 }, Point { x: x1, y: y1 }, color);
             }
+            LineStyle::Thick(line_width) => self.thick_lines(&edges, line_width),
         }
     }
 
     pub(crate) fn render_random(&mut self, wireframe: WireframeModel) {
-        for face in wireframe.faces {
+        for i in wireframe.visible_faces() {
+            let face = wireframe.faces[i];
             let world_coords = face.map(|f| wireframe.vertexes[f.vertex_index]);
             let pts = RGBImage::screen_triangle(world_coords, self.width, self.height);
             self.triangle_filed(pts, RGBColor::random());
@@ -31,7 +83,8 @@ impl RGBImage {
     }
 
     pub(crate) fn render_light(&mut self, wireframe: WireframeModel, light_dir: Vec3<f32>) {
-        for face in wireframe.faces {
+        for i in wireframe.visible_faces() {
+            let face = wireframe.faces[i];
             let world_coords = face.map(|f| wireframe.vertexes[f.vertex_index]);
             let n = cross(
                 diff(world_coords[2], world_coords[0]),
@@ -49,188 +102,278 @@ impl RGBImage {
         }
     }
 
-    pub(crate) fn render_z_buffer(&mut self, wireframe: WireframeModel, light_dir: Vec3<f32>) {
-        let z_buffer_size: i32 = self.width as i32 * self.height as i32;
-        let mut z_buffer: Vec<f32> = (0..z_buffer_size).map(|_x| -1.0).collect();
+    // Flat, Gouraud and normal mapping lessons are lit by a single white directional light,
+    // see `render_phong` for several colored lights
+    pub(crate) fn render_z_buffer(
+        &mut self,
+        wireframe: WireframeModel,
+        light_dir: Vec3<f32>,
+    ) -> DepthBuffer {
+        self.render_z_buffer_colored(&wireframe, light_dir, &|_| WHITE_COLOR)
+    }
 
-        for face in wireframe.faces {
-            let world_coords = face.map(|f| wireframe.vertexes[f.vertex_index]);
-            let n = cross(
-                diff(world_coords[2], world_coords[0]),
-                diff(world_coords[1], world_coords[0]),
-            )
-            .normalized();
-            let intensity = dot_product(light_dir, n);
+    // Every group has its own color
+    pub fn render_z_buffer_groups(
+        &mut self,
+        wireframe: WireframeModel,
+        light_dir: Vec3<f32>,
+    ) -> DepthBuffer {
+        let face_colors = wireframe.face_colors();
+        self.render_z_buffer_colored(&wireframe, light_dir, &|face| face_colors[face])
+    }
 
-            if intensity > 0.0 {
-                let pts = RGBImage::screen_triangle_3d(world_coords, self.width, self.height);
-                self.triangle_z_buffer(pts, &mut z_buffer, RGBColor::intensity(intensity));
-            }
-        }
+    fn render_z_buffer_colored(
+        &mut self,
+        wireframe: &WireframeModel,
+        light_dir: Vec3<f32>,
+        face_color: &dyn Fn(usize) -> RGBColor,
+    ) -> DepthBuffer {
+        let projection = RGBImage::clip_vertex;
+        let mut shader = FlatShader {
+            light_dir,
+            projection: &projection,
+            face_color,
+            world_coords: [Vec3::default(); 3],
+            color: WHITE_COLOR,
+            intensity: 0.0,
+        };
+        self.draw(wireframe, &mut shader)
     }
 
     pub(crate) fn render_z_buffer_texture(
         &mut self,
         wireframe: WireframeModel,
-        texture: DynamicImage,
+        texture: Texture,
         light_dir: Vec3<f32>,
-    ) {
-        let w = self.width;
-        let h = self.height;
-        let projection =
-            |world_coords: [Vec3<f32>; 3]| RGBImage::screen_triangle_3d(world_coords, w, h);
-        self.render_z_buffer_texture_projection(wireframe, texture, light_dir, &projection);
+    ) -> DepthBuffer {
+        let projection = RGBImage::clip_vertex;
+        self.render_z_buffer_texture_projection(wireframe, texture, light_dir, &projection)
     }
 
     pub(crate) fn render_z_buffer_texture_perspective(
         &mut self,
         wireframe: WireframeModel,
-        texture: DynamicImage,
+        texture: Texture,
         light_dir: Vec3<f32>,
-    ) {
-        let mut projection_matrix = Matrix::new_identity(4);
-        let camera_z = 3.0;
-        projection_matrix.m[3][2] = -1.0 / camera_z;
-        let view_port = ViewPort {
-            x: self.width / 8,
-            y: self.height / 8,
-            width: self.width * 3 / 4,
-            height: self.height * 3 / 4,
-        };
-        let projection_viewport = view_port.to_matrix() * projection_matrix;
-        let projection = |world_coords: [Vec3<f32>; 3]| {
-            RGBImage::screen_triangle_3d_perspective(world_coords, projection_viewport.clone())
-        };
-        self.render_z_buffer_texture_projection(wireframe, texture, light_dir, &projection);
+        camera: &Camera,
+    ) -> DepthBuffer {
+        let z = camera.view_projection();
+        let projection = |v: Vec3<f32>| z.homogeneous(v);
+        self.render_z_buffer_texture_projection(wireframe, texture, light_dir, &projection)
     }
 
     fn render_z_buffer_texture_projection(
         &mut self,
         wireframe: WireframeModel,
-        texture: DynamicImage,
+        texture: Texture,
         light_dir: Vec3<f32>,
-        projection: &dyn Fn([Vec3<f32>; 3]) -> [Vec3<u16>; 3],
-    ) {
-        let z_buffer_size: i32 = self.width as i32 * self.height as i32;
-        let mut z_buffer: Vec<f32> = (0..z_buffer_size).map(|_x| -1.0).collect();
+        projection: &dyn Fn(Vec3<f32>) -> Vec4<f32>,
+    ) -> DepthBuffer {
+        let mut shader = TextureShader {
+            diffuse: DiffuseColor::new(&texture, &wireframe.materials),
+            light_dir,
+            projection,
+            world_coords: [Vec3::default(); 3],
+            intensity: 0.0,
+        };
+        self.draw(&wireframe, &mut shader)
+    }
 
-        for face in &wireframe.faces {
-            let world_coords = face.map(|f| wireframe.vertexes[f.vertex_index]);
-            let n = cross(
-                diff(world_coords[2], world_coords[0]),
-                diff(world_coords[1], world_coords[0]),
-            )
-            .normalized();
-            let intensity = dot_product(light_dir, n);
+    pub(crate) fn render_z_buffer_texture_perspective_gouraud(
+        &mut self,
+        wireframe: WireframeModel,
+        texture: Texture,
+        light_dir: Vec3<f32>,
+        camera: &Camera,
+    ) -> DepthBuffer {
+        let z = camera.view_projection();
+        let projection = |v: Vec3<f32>| z.homogeneous(v);
 
-            let texture_coords =
-                wireframe.texture_coord(face.clone(), texture.width(), texture.height());
-            let texture_color = |bc: Vec3<f32>| -> RGBColor {
-                let uv = Vec2 {
-                    x: texture_coords[0].x * bc.x
-                        + texture_coords[1].x * bc.y
-                        + texture_coords[2].x * bc.z,
-                    y: texture_coords[0].y * bc.x
-                        + texture_coords[1].y * bc.y
-                        + texture_coords[2].y * bc.z,
-                };
-                let pixel = texture.get_pixel(uv.x as u32, uv.y as u32);
-                let color = RGBColor {
-                    r: pixel.0[0],
-                    g: pixel.0[1],
-                    b: pixel.0[2],
-                }
-                .with_intensity(intensity);
-                return color;
-            };
-            if intensity > 0.0 {
-                let pts = projection(world_coords);
-                self.triangle_z_buffer_bary(pts, &mut z_buffer, &texture_color);
-            }
+        let mut shader = GouraudShader {
+            diffuse: DiffuseColor::new(&texture, &wireframe.materials),
+            light_dir,
+            projection: &projection,
+            intensity: [0.0; 3],
+        };
+        self.draw(&wireframe, &mut shader)
+    }
+
+    // Same camera as Gouraud shading, normals are perturbed per pixel with the tangent space
+    // normal map, faces with a material use its `bump_map` instead
+    pub(crate) fn render_normal_mapping(
+        &mut self,
+        mut wireframe: WireframeModel,
+        texture: Texture,
+        normal_map: Option<Texture>,
+        light_dir: Vec3<f32>,
+        camera: &Camera,
+    ) -> DepthBuffer {
+        if wireframe.face_tangents.len() != wireframe.faces.len() {
+            wireframe.compute_tangents();
         }
+        let z = camera.view_projection();
+        let projection = |v: Vec3<f32>| z.homogeneous(v);
+
+        let mut shader = NormalMapShader {
+            diffuse: DiffuseColor::new(&texture, &wireframe.materials),
+            normal_map: normal_map.as_ref(),
+            light_dir: light_dir.normalized(),
+            projection: &projection,
+            normals: [Vec3::default(); 3],
+            tangents: [Vec3::default(); 3],
+            bitangents: [Vec3::default(); 3],
+        };
+        self.draw(&wireframe, &mut shader)
     }
 
-    pub(crate) fn render_z_buffer_texture_perspective_gouraud(
+    // Per pixel ambient, diffuse and specular lighting with interpolated vertex normals,
+    // the specular exponent is taken from the specular map if there is one
+    pub fn render_phong(
+        &mut self,
+        wireframe: WireframeModel,
+        texture: Texture,
+        specular_map: Option<Texture>,
+        phong: Phong,
+        lights: &[Light],
+        camera: &Camera,
+    ) -> DepthBuffer {
+        self.render_phong_shadow_maps(
+            &wireframe,
+            texture,
+            specular_map,
+            phong,
+            lights,
+            camera,
+            &[],
+        )
+    }
+
+    /// Phong shading with shadows of directional lights, the first pass renders depth
+    /// from every such light, the second one compares pixels with it.
+    /// Returns light depth buffers (shadow maps) of directional lights in order.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_shadows(
         &mut self,
         wireframe: WireframeModel,
-        texture: DynamicImage,
+        texture: Texture,
+        specular_map: Option<Texture>,
+        phong: Phong,
+        lights: &[Light],
+        camera: &Camera,
+        shadow: Shadow,
+    ) -> Vec<DepthBuffer> {
+        let shadow_maps: Vec<Option<ShadowMap>> = lights
+            .iter()
+            .map(|light| match light {
+                Light::Directional { direction, .. } => {
+                    Some(self.shadow_map(&wireframe, *direction, shadow))
+                }
+                _ => None,
+            })
+            .collect();
+        self.render_phong_shadow_maps(
+            &wireframe,
+            texture,
+            specular_map,
+            phong,
+            lights,
+            camera,
+            &shadow_maps,
+        );
+        shadow_maps
+            .into_iter()
+            .flatten()
+            .map(|shadow_map| shadow_map.depth)
+            .collect()
+    }
+
+    fn shadow_map(
+        &self,
+        wireframe: &WireframeModel,
         light_dir: Vec3<f32>,
-    ) {
-        let eye = Vec3 {
-            x: 1.0,
-            y: 1.0,
-            z: 3.0,
+        shadow: Shadow,
+    ) -> ShadowMap {
+        let clip = self.light_projection(light_dir);
+        let projection = |v: Vec3<f32>| clip.homogeneous(v);
+        let mut depth_pass = RGBImage::new(self.width, self.height, BLACK_COLOR);
+        let depth = depth_pass.draw(
+            wireframe,
+            &mut DepthShader {
+                projection: &projection,
+            },
+        );
+        ShadowMap {
+            depth,
+            matrix: self.view_port().to_matrix() * clip,
+            shadow,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn render_phong_shadow_maps(
+        &mut self,
+        wireframe: &WireframeModel,
+        texture: Texture,
+        specular_map: Option<Texture>,
+        phong: Phong,
+        lights: &[Light],
+        camera: &Camera,
+        shadow_maps: &[Option<ShadowMap>],
+    ) -> DepthBuffer {
+        let z = camera.view_projection();
+        let projection = |v: Vec3<f32>| z.homogeneous(v);
+
+        let mut shader = PhongShader {
+            shadow_maps,
+            ..PhongShader::new(
+                DiffuseColor::new(&texture, &wireframe.materials),
+                specular_map.as_ref(),
+                phong,
+                lights,
+                camera,
+                &projection,
+            )
         };
+        self.draw(wireframe, &mut shader)
+    }
+
+    // Directional light has no perspective, the scene is viewed along `light_dir`
+    // and scaled down to fit into the shadow map
+    fn light_projection(&self, light_dir: Vec3<f32>) -> Matrix {
+        let light_dir = light_dir.normalized();
         let center = Vec3 {
             x: 0.0,
             y: 0.0,
             z: 0.0,
         };
-        let model_view = look_at(
-            eye,
-            center,
+        // `up` can't be parallel to the view direction
+        let up = if light_dir.y.abs() > 0.99 {
+            Vec3 {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            }
+        } else {
             Vec3 {
                 x: 0.0,
                 y: 1.0,
                 z: 0.0,
-            },
-        );
-        let mut projection_matrix = Matrix::new_identity(4);
-        let camera_z = 3.0;
-        projection_matrix.m[3][2] = -1.0 / camera_z;
-        let view_port = ViewPort {
-            x: self.width / 8,
-            y: self.height / 8,
-            width: self.width * 3 / 4,
-            height: self.height * 3 / 4,
-        };
-
-        let z = view_port.to_matrix() * projection_matrix * model_view;
-        let projection = |world_coords: [Vec3<f32>; 3]| {
-            RGBImage::screen_triangle_3d_perspective(world_coords, z.clone())
+            }
         };
+        let mut scale = Matrix::new_identity(4);
+        for i in 0..3 {
+            scale.m[i][i] = 0.75;
+        }
+        scale * look_at(light_dir, center, up)
+    }
 
-        let z_buffer_size: i32 = self.width as i32 * self.height as i32;
-        let mut z_buffer: Vec<f32> = (0..z_buffer_size).map(|_x| -1.0).collect();
-
-        for face in &wireframe.faces {
-            let world_coords = face.map(|f| wireframe.vertexes[f.vertex_index]);
-            let intensity = face
-                .map(|f| wireframe.norm[f.norm_index])
-                .map(|mut n: Vec3<f32>| {
-                    n.normalize();
-                    return light_dir.x * n.x + light_dir.y * n.y + light_dir.z * n.z;
-                });
-
-            let texture_coords =
-                wireframe.texture_coord(face.clone(), texture.width(), texture.height());
-            let texture_color = |bc: Vec3<f32>| -> RGBColor {
-                let uv = Vec2 {
-                    x: texture_coords[0].x * bc.x
-                        + texture_coords[1].x * bc.y
-                        + texture_coords[2].x * bc.z,
-                    y: texture_coords[0].y * bc.x
-                        + texture_coords[1].y * bc.y
-                        + texture_coords[2].y * bc.z,
-                };
-                let weighted_intensity =
-                    intensity[0] * bc.x + intensity[1] * bc.y + intensity[2] * bc.z;
-
-                let pixel = texture.get_pixel(uv.x as u32, uv.y as u32);
-                let color = RGBColor {
-                    r: pixel.0[0],
-                    g: pixel.0[1],
-                    b: pixel.0[2],
-                };
-
-                return if weighted_intensity > 0.0 {
-                    color.with_intensity(weighted_intensity)
-                } else {
-                    BLACK_COLOR
-                };
-            };
-            let pts = projection(world_coords);
-            self.triangle_z_buffer_bary(pts, &mut z_buffer, &texture_color);
+    // Clip coordinates to the whole image, same as `draw` does
+    fn view_port(&self) -> ViewPort {
+        ViewPort {
+            x: 0,
+            y: 0,
+            width: self.width,
+            height: self.height,
         }
     }
 
@@ -246,33 +389,583 @@ impl RGBImage {
         ]
     }
 
-    fn screen_triangle_3d(world_coords: [Vec3<f32>; 3], width: u16, height: u16) -> [Vec3<u16>; 3] {
-        let projection = |world_coords: Vec3<f32>| Vec3::<u16> {
-            x: ((world_coords.x + 1.0) * (width as f32) / 2.0) as u16,
-            y: ((world_coords.y + 1.0) * (height as f32) / 2.0) as u16,
-            z: world_coords.z as u16,
+    // The model is already in the [-1, 1] cube, it's projected along `z`
+    fn clip_vertex(world_coords: Vec3<f32>) -> Vec4<f32> {
+        Vec4 {
+            x: world_coords.x,
+            y: world_coords.y,
+            z: world_coords.z,
+            w: 1.0,
+        }
+    }
+}
+
+impl RGBAImage {
+    /// Phong shading of faces with texture alpha (or material `dissolve`) blended into the image
+    /// in back to front order, e.g. glass or foliage cards.
+    pub fn render_transparent(
+        &mut self,
+        wireframe: WireframeModel,
+        texture: Texture,
+        phong: Phong,
+        lights: &[Light],
+        camera: &Camera,
+        blending: Blending,
+    ) -> DepthBuffer {
+        let z = camera.view_projection();
+        let projection = |v: Vec3<f32>| z.homogeneous(v);
+        let mut shader = PhongShader::new(
+            DiffuseColor::new(&texture, &wireframe.materials),
+            None,
+            phong,
+            lights,
+            camera,
+            &projection,
+        );
+        let faces = camera.back_to_front(&wireframe, wireframe.visible_faces());
+        let mut depth = DepthBuffer::new(self.width, self.height);
+        self.draw_faces(&wireframe, faces, &mut shader, blending, &mut depth);
+        depth
+    }
+}
+
+impl HDRImage {
+    /// Same as `RGBImage::render_phong` but the light is accumulated in linear space
+    /// without clamping, the image should be resolved with a tone mapping afterwards.
+    pub fn render_phong(
+        &mut self,
+        wireframe: WireframeModel,
+        texture: Texture,
+        specular_map: Option<Texture>,
+        phong: Phong,
+        lights: &[Light],
+        camera: &Camera,
+    ) -> DepthBuffer {
+        let z = camera.view_projection();
+        let projection = |v: Vec3<f32>| z.homogeneous(v);
+        let mut shader = PhongShader::new(
+            DiffuseColor::new(&texture, &wireframe.materials),
+            specular_map.as_ref(),
+            phong,
+            lights,
+            camera,
+            &projection,
+        );
+        self.draw(&wireframe, &mut shader)
+    }
+}
+
+// Lambert shading with a single normal per face
+struct FlatShader<'a> {
+    light_dir: Vec3<f32>,
+    projection: &'a dyn Fn(Vec3<f32>) -> Vec4<f32>,
+    face_color: &'a dyn Fn(usize) -> RGBColor,
+    world_coords: [Vec3<f32>; 3],
+    color: RGBColor,
+    intensity: f32,
+}
+
+impl Shader for FlatShader<'_> {
+    fn vertex(&mut self, model: &WireframeModel, face: usize, nth_vertex: usize) -> Vec4<f32> {
+        let v = model.vertexes[model.faces[face][nth_vertex].vertex_index];
+        self.world_coords[nth_vertex] = v;
+        if nth_vertex == 2 {
+            self.intensity = face_intensity(self.world_coords, self.light_dir);
+            self.color = (self.face_color)(face);
+        }
+        (self.projection)(v)
+    }
+
+    fn fragment(&self, _bc: Vec3<f32>) -> Option<RGBColor> {
+        if self.intensity > 0.0 {
+            Some(self.color.with_intensity(self.intensity))
+        } else {
+            None
+        }
+    }
+}
+
+// Diffuse color of the current face, the face material takes precedence over the model texture
+struct DiffuseColor<'a> {
+    texture: &'a Texture,
+    materials: &'a [Material],
+    material: Option<&'a Material>,
+    uv: [Option<Vec2<f32>>; 3],
+    window_coords: [Vec4<f32>; 3],
+}
+
+impl<'a> DiffuseColor<'a> {
+    fn new(texture: &'a Texture, materials: &'a [Material]) -> Self {
+        DiffuseColor {
+            texture,
+            materials,
+            material: None,
+            uv: [None; 3],
+            window_coords: [Vec4::default(); 3],
+        }
+    }
+
+    fn diffuse_map(&self) -> Option<&'a Texture> {
+        match self.material {
+            Some(material) => material.diffuse_map.as_ref(),
+            None => Some(self.texture),
+        }
+    }
+
+    fn vertex(&mut self, model: &WireframeModel, face: usize, nth_vertex: usize) {
+        if nth_vertex == 0 {
+            self.material = model.face_materials[face].map(|m| &self.materials[m]);
+        }
+        self.uv[nth_vertex] = model.texture_coord(model.faces[face][nth_vertex]);
+    }
+
+    // Texture of the face sampled at the pixel, faces without texture coordinates are white
+    fn sample(&self, texture: &Texture, bc: Vec3<f32>) -> RGBAColor {
+        let uv = match self.uv {
+            [Some(uv0), Some(uv1), Some(uv2)] => [uv0, uv1, uv2],
+            _ => return RGBAColor::from(WHITE_COLOR),
         };
-        [
-            projection(world_coords[0]),
-            projection(world_coords[1]),
-            projection(world_coords[2]),
-        ]
+        let (duv_dx, duv_dy) = uv_derivatives(self.window_coords, uv, bc);
+        texture.sample(interpolate(uv, bc), texture.lod(duv_dx, duv_dy))
     }
 
-    fn screen_triangle_3d_perspective(
-        world_coords: [Vec3<f32>; 3],
-        projection_matrix: Matrix,
-    ) -> [Vec3<u16>; 3] {
-        let projection = |world_coords: Vec3<f32>| {
-            (projection_matrix.clone() * world_coords.to_matrix())
-                .to_vector()
-                .as_u16()
+    fn fragment(&self, bc: Vec3<f32>) -> RGBColor {
+        let color = match self.diffuse_map() {
+            Some(texture) => self.sample(texture, bc).rgb(),
+            None => WHITE_COLOR,
         };
-        [
-            projection(world_coords[0]),
-            projection(world_coords[1]),
-            projection(world_coords[2]),
-        ]
+        match self.material {
+            Some(material) => color.tinted(material.diffuse),
+            None => color,
+        }
+    }
+
+    // Texture alpha multiplied by the material `dissolve`
+    fn alpha(&self, bc: Vec3<f32>) -> u8 {
+        let alpha = match self.diffuse_map() {
+            Some(texture) => self.sample(texture, bc).a,
+            None => 255,
+        };
+        match self.material {
+            Some(material) => (alpha as f32 * material.dissolve.clamp(0.0, 1.0)) as u8,
+            None => alpha,
+        }
+    }
+
+    // Shaded color of the pixel with the surface alpha
+    fn with_alpha(&self, color: Option<RGBColor>, bc: Vec3<f32>) -> Option<RGBAColor> {
+        color.map(|color| color.with_alpha(self.alpha(bc)))
+    }
+}
+
+// Same as `FlatShader` but takes pixel colors from the texture
+struct TextureShader<'a> {
+    diffuse: DiffuseColor<'a>,
+    light_dir: Vec3<f32>,
+    projection: &'a dyn Fn(Vec3<f32>) -> Vec4<f32>,
+    world_coords: [Vec3<f32>; 3],
+    intensity: f32,
+}
+
+impl Shader for TextureShader<'_> {
+    fn vertex(&mut self, model: &WireframeModel, face: usize, nth_vertex: usize) -> Vec4<f32> {
+        let v = model.vertexes[model.faces[face][nth_vertex].vertex_index];
+        self.world_coords[nth_vertex] = v;
+        self.diffuse.vertex(model, face, nth_vertex);
+        if nth_vertex == 2 {
+            self.intensity = face_intensity(self.world_coords, self.light_dir);
+        }
+        (self.projection)(v)
+    }
+
+    fn window_coords(&mut self, coords: [Vec4<f32>; 3]) {
+        self.diffuse.window_coords = coords;
+    }
+
+    fn fragment(&self, bc: Vec3<f32>) -> Option<RGBColor> {
+        if self.intensity > 0.0 {
+            let color = self.diffuse.fragment(bc);
+            Some(color.with_intensity(self.intensity))
+        } else {
+            None
+        }
+    }
+
+    fn fragment_rgba(&self, bc: Vec3<f32>) -> Option<RGBAColor> {
+        self.diffuse.with_alpha(self.fragment(bc), bc)
+    }
+}
+
+// Interpolates light intensity calculated for every vertex normal
+struct GouraudShader<'a> {
+    diffuse: DiffuseColor<'a>,
+    light_dir: Vec3<f32>,
+    projection: &'a dyn Fn(Vec3<f32>) -> Vec4<f32>,
+    intensity: [f32; 3],
+}
+
+impl GouraudShader<'_> {
+    fn weighted_intensity(&self, bc: Vec3<f32>) -> f32 {
+        self.intensity[0] * bc.x + self.intensity[1] * bc.y + self.intensity[2] * bc.z
+    }
+}
+
+impl Shader for GouraudShader<'_> {
+    fn vertex(&mut self, model: &WireframeModel, face: usize, nth_vertex: usize) -> Vec4<f32> {
+        let f = model.faces[face][nth_vertex];
+        let mut n = match f.norm_index {
+            Some(i) => model.norm[i],
+            None => model.face_normal(face),
+        };
+        n.normalize();
+        self.intensity[nth_vertex] = dot_product(self.light_dir, n);
+        self.diffuse.vertex(model, face, nth_vertex);
+        (self.projection)(model.vertexes[f.vertex_index])
+    }
+
+    fn window_coords(&mut self, coords: [Vec4<f32>; 3]) {
+        self.diffuse.window_coords = coords;
+    }
+
+    fn fragment(&self, bc: Vec3<f32>) -> Option<RGBColor> {
+        let weighted_intensity = self.weighted_intensity(bc);
+        let color = self.diffuse.fragment(bc);
+        if weighted_intensity > 0.0 {
+            Some(color.with_intensity(weighted_intensity))
+        } else {
+            Some(BLACK_COLOR)
+        }
+    }
+
+    fn fragment_hdr(&self, bc: Vec3<f32>) -> Option<Vec3<f32>> {
+        Some(linear_intensity(
+            self.diffuse.fragment(bc),
+            self.weighted_intensity(bc),
+        ))
+    }
+
+    fn fragment_rgba(&self, bc: Vec3<f32>) -> Option<RGBAColor> {
+        self.diffuse.with_alpha(self.fragment(bc), bc)
+    }
+}
+
+// Interpolates tangent space of the face and rotates normal map normals with it
+struct NormalMapShader<'a> {
+    diffuse: DiffuseColor<'a>,
+    normal_map: Option<&'a Texture>,
+    light_dir: Vec3<f32>,
+    projection: &'a dyn Fn(Vec3<f32>) -> Vec4<f32>,
+    normals: [Vec3<f32>; 3],
+    tangents: [Vec3<f32>; 3],
+    bitangents: [Vec3<f32>; 3],
+}
+
+impl NormalMapShader<'_> {
+    fn normal_map(&self) -> Option<&Texture> {
+        match self.diffuse.material {
+            Some(material) => material.bump_map.as_ref(),
+            None => self.normal_map,
+        }
+    }
+
+    // Lambert term of the normal from the normal map
+    fn intensity(&self, bc: Vec3<f32>) -> f32 {
+        let n = interpolate_vec3(self.normals, bc);
+        let n = match self.normal_map() {
+            Some(texture) if self.diffuse.uv.iter().all(|uv| uv.is_some()) => {
+                // 0..255 color channels are -1..1 tangent space coordinates
+                let c = self.diffuse.sample(texture, bc);
+                let [x, y, z] = [c.r, c.g, c.b].map(|c| c as f32 / 255.0 * 2.0 - 1.0);
+                let t = interpolate_vec3(self.tangents, bc);
+                let b = interpolate_vec3(self.bitangents, bc);
+                Vec3 {
+                    x: t.x * x + b.x * y + n.x * z,
+                    y: t.y * x + b.y * y + n.y * z,
+                    z: t.z * x + b.z * y + n.z * z,
+                }
+            }
+            _ => n,
+        };
+        dot_product(self.light_dir, n.normalized())
+    }
+}
+
+impl Shader for NormalMapShader<'_> {
+    fn vertex(&mut self, model: &WireframeModel, face: usize, nth_vertex: usize) -> Vec4<f32> {
+        let f = model.faces[face][nth_vertex];
+        self.diffuse.vertex(model, face, nth_vertex);
+        let n = match f.norm_index {
+            Some(i) => model.norm[i],
+            None => model.face_normal(face),
+        };
+        let n = n.normalized();
+        let tangent = model.face_tangents[face][nth_vertex];
+        self.normals[nth_vertex] = n;
+        self.tangents[nth_vertex] = tangent.tangent;
+        self.bitangents[nth_vertex] = tangent.bitangent(n);
+        (self.projection)(model.vertexes[f.vertex_index])
+    }
+
+    fn window_coords(&mut self, coords: [Vec4<f32>; 3]) {
+        self.diffuse.window_coords = coords;
+    }
+
+    fn fragment(&self, bc: Vec3<f32>) -> Option<RGBColor> {
+        let intensity = self.intensity(bc);
+        let color = self.diffuse.fragment(bc);
+        if intensity > 0.0 {
+            Some(color.with_intensity(intensity))
+        } else {
+            Some(BLACK_COLOR)
+        }
+    }
+
+    fn fragment_hdr(&self, bc: Vec3<f32>) -> Option<Vec3<f32>> {
+        Some(linear_intensity(
+            self.diffuse.fragment(bc),
+            self.intensity(bc),
+        ))
+    }
+
+    fn fragment_rgba(&self, bc: Vec3<f32>) -> Option<RGBAColor> {
+        self.diffuse.with_alpha(self.fragment(bc), bc)
+    }
+}
+
+// Phong reflection model evaluated for every pixel
+struct PhongShader<'a> {
+    diffuse: DiffuseColor<'a>,
+    specular_map: Option<&'a Texture>,
+    phong: Phong,
+    lights: &'a [Light],
+    camera: &'a Camera,
+    projection: &'a dyn Fn(Vec3<f32>) -> Vec4<f32>,
+    normals: [Vec3<f32>; 3],
+    world_coords: [Vec3<f32>; 3],
+    /// Shadow map for every light, only directional lights have one, empty without shadows
+    shadow_maps: &'a [Option<ShadowMap>],
+}
+
+impl<'a> PhongShader<'a> {
+    fn new(
+        diffuse: DiffuseColor<'a>,
+        specular_map: Option<&'a Texture>,
+        phong: Phong,
+        lights: &'a [Light],
+        camera: &'a Camera,
+        projection: &'a dyn Fn(Vec3<f32>) -> Vec4<f32>,
+    ) -> Self {
+        PhongShader {
+            diffuse,
+            specular_map,
+            phong,
+            lights,
+            camera,
+            projection,
+            normals: [Vec3::default(); 3],
+            world_coords: [Vec3::default(); 3],
+            shadow_maps: &[],
+        }
+    }
+
+    fn specular_map(&self) -> Option<&Texture> {
+        match self.diffuse.material {
+            Some(material) => material.specular_map.as_ref(),
+            None => self.specular_map,
+        }
+    }
+
+    fn shininess(&self, bc: Vec3<f32>) -> f32 {
+        match (self.specular_map(), self.diffuse.material) {
+            (Some(texture), _) if self.diffuse.uv.iter().all(|uv| uv.is_some()) => {
+                self.diffuse.sample(texture, bc).r as f32
+            }
+            (_, Some(material)) => material.shininess,
+            _ => self.phong.shininess,
+        }
+    }
+
+    // Diffuse and specular light summed over all sources, not clamped
+    fn lighting(&self, bc: Vec3<f32>) -> (Vec3<f32>, Vec3<f32>) {
+        let n = interpolate_vec3(self.normals, bc).normalized();
+        let p = interpolate_vec3(self.world_coords, bc);
+        let v = self.camera.view_direction(p);
+        let shininess = self.shininess(bc).max(1.0);
+        let ks = self.specular_color();
+
+        // ambient is not scaled by light colors, so surfaces stay visible with no light
+        let ambient = self.phong.ambient;
+        let mut diffuse = Vec3 {
+            x: ambient,
+            y: ambient,
+            z: ambient,
+        };
+        let mut specular = Vec3::default();
+        for (i, light) in self.lights.iter().enumerate() {
+            let (l, color) = light.illuminate(p);
+            let n_dot_l = dot_product(n, l);
+            if n_dot_l <= 0.0 {
+                continue;
+            }
+            let cos = if self.phong.blinn {
+                let h = Vec3 {
+                    x: l.x + v.x,
+                    y: l.y + v.y,
+                    z: l.z + v.z,
+                };
+                dot_product(n, h.normalized())
+            } else {
+                // light reflected by the surface
+                let r = Vec3 {
+                    x: 2.0 * n_dot_l * n.x - l.x,
+                    y: 2.0 * n_dot_l * n.y - l.y,
+                    z: 2.0 * n_dot_l * n.z - l.z,
+                };
+                dot_product(r, v)
+            };
+            let lit = match self.shadow_maps.get(i) {
+                Some(Some(shadow_map)) => shadow_map.light(p),
+                _ => 1.0,
+            };
+            let kd = self.phong.diffuse * n_dot_l * lit;
+            let spec = cos.max(0.0).powf(shininess) * lit;
+            diffuse.x += kd * color.x;
+            diffuse.y += kd * color.y;
+            diffuse.z += kd * color.z;
+            specular.x += ks.x * spec * color.x;
+            specular.y += ks.y * spec * color.y;
+            specular.z += ks.z * spec * color.z;
+        }
+        (diffuse, specular)
+    }
+
+    // Material Ks or the same value for all channels
+    fn specular_color(&self) -> Vec3<f32> {
+        match self.diffuse.material {
+            Some(material) => material.specular,
+            None => Vec3 {
+                x: self.phong.specular,
+                y: self.phong.specular,
+                z: self.phong.specular,
+            },
+        }
+    }
+}
+
+impl Shader for PhongShader<'_> {
+    fn vertex(&mut self, model: &WireframeModel, face: usize, nth_vertex: usize) -> Vec4<f32> {
+        let f = model.faces[face][nth_vertex];
+        self.diffuse.vertex(model, face, nth_vertex);
+        let n = match f.norm_index {
+            Some(i) => model.norm[i],
+            None => model.face_normal(face),
+        };
+        self.normals[nth_vertex] = n.normalized();
+        let v = model.vertexes[f.vertex_index];
+        self.world_coords[nth_vertex] = v;
+        (self.projection)(v)
+    }
+
+    fn window_coords(&mut self, coords: [Vec4<f32>; 3]) {
+        self.diffuse.window_coords = coords;
+    }
+
+    fn fragment(&self, bc: Vec3<f32>) -> Option<RGBColor> {
+        let (diffuse, specular) = self.lighting(bc);
+        Some(self.diffuse.fragment(bc).shaded(diffuse, specular))
+    }
+
+    fn fragment_hdr(&self, bc: Vec3<f32>) -> Option<Vec3<f32>> {
+        let (diffuse, specular) = self.lighting(bc);
+        let color = self.diffuse.fragment(bc).to_linear();
+        Some(Vec3 {
+            x: color.x * diffuse.x + specular.x,
+            y: color.y * diffuse.y + specular.y,
+            z: color.z * diffuse.z + specular.z,
+        })
+    }
+
+    fn fragment_rgba(&self, bc: Vec3<f32>) -> Option<RGBAColor> {
+        self.diffuse.with_alpha(self.fragment(bc), bc)
+    }
+}
+
+// Only writes depth, colors of the depth pass are not used
+struct DepthShader<'a> {
+    projection: &'a dyn Fn(Vec3<f32>) -> Vec4<f32>,
+}
+
+impl Shader for DepthShader<'_> {
+    fn vertex(&mut self, model: &WireframeModel, face: usize, nth_vertex: usize) -> Vec4<f32> {
+        let v = model.vertexes[model.faces[face][nth_vertex].vertex_index];
+        (self.projection)(v)
+    }
+
+    fn fragment(&self, _bc: Vec3<f32>) -> Option<RGBColor> {
+        Some(WHITE_COLOR)
+    }
+}
+
+// Depth of the scene rendered from the light
+struct ShadowMap {
+    depth: DepthBuffer,
+    /// World to shadow map coordinates
+    matrix: Matrix,
+    shadow: Shadow,
+}
+
+impl ShadowMap {
+    // Fraction of shadow map samples around the point which see the light
+    fn light(&self, p: Vec3<f32>) -> f32 {
+        let s = self.matrix.transform(p);
+        let r = self.shadow.pcf_radius as i32;
+        let (x, y) = (s.x as i32, s.y as i32);
+        let mut lit = 0;
+        for dx in -r..=r {
+            for dy in -r..=r {
+                // nothing is rendered outside of the shadow map, so it's lit
+                let closest = self.depth.get(x + dx, y + dy).unwrap_or(-1.0);
+                if s.z + self.shadow.bias >= closest {
+                    lit += 1;
+                }
+            }
+        }
+        lit as f32 / ((2 * r + 1) * (2 * r + 1)) as f32
+    }
+}
+
+fn face_intensity(world_coords: [Vec3<f32>; 3], light_dir: Vec3<f32>) -> f32 {
+    let n = cross(
+        diff(world_coords[2], world_coords[0]),
+        diff(world_coords[1], world_coords[0]),
+    )
+    .normalized();
+    dot_product(light_dir, n)
+}
+
+// Linear color scaled by the light intensity, negative intensity is black
+fn linear_intensity(color: RGBColor, intensity: f32) -> Vec3<f32> {
+    let color = color.to_linear();
+    let i = intensity.max(0.0);
+    Vec3 {
+        x: color.x * i,
+        y: color.y * i,
+        z: color.z * i,
+    }
+}
+
+fn interpolate(values: [Vec2<f32>; 3], bc: Vec3<f32>) -> Vec2<f32> {
+    Vec2 {
+        x: values[0].x * bc.x + values[1].x * bc.y + values[2].x * bc.z,
+        y: values[0].y * bc.x + values[1].y * bc.y + values[2].y * bc.z,
+    }
+}
+
+fn interpolate_vec3(values: [Vec3<f32>; 3], bc: Vec3<f32>) -> Vec3<f32> {
+    Vec3 {
+        x: values[0].x * bc.x + values[1].x * bc.y + values[2].x * bc.z,
+        y: values[0].y * bc.x + values[1].y * bc.y + values[2].y * bc.z,
+        z: values[0].z * bc.x + values[1].z * bc.y + values[2].z * bc.z,
     }
 }
 
@@ -284,11 +977,154 @@ impl FromIterator<Point> for [Point; 3] {
 }
 
 impl WireframeModel {
-    fn texture_coord(&self, face: [Face; 3], width: u32, height: u32) -> [Vec2<f32>; 3] {
-        face.map(|f| self.texture_coord[f.texture_index])
-            .map(|p| Vec2 {
-                x: width as f32 * p.0,
-                y: height as f32 * p.1,
-            })
+    fn texture_coord(&self, face: Face) -> Option<Vec2<f32>> {
+        let p = self.texture_coord[face.texture_index?];
+        Some(Vec2 { x: p.0, y: p.1 })
+    }
+
+    fn face_colors(&self) -> Vec<RGBColor> {
+        self.face_groups()
+            .iter()
+            .map(|g| g.map_or(WHITE_COLOR, RGBColor::indexed))
+            .collect()
+    }
+
+    fn polygon_colors(&self) -> Vec<RGBColor> {
+        let mut colors = vec![WHITE_COLOR; self.polygons.len()];
+        for (i, group) in self.groups.iter().enumerate() {
+            for range in &group.polygons {
+                colors[range.clone()].fill(RGBColor::indexed(i));
+            }
+        }
+        colors
+    }
+
+    // Flat normal used when the model doesn't have vertex normals
+    fn face_normal(&self, face: usize) -> Vec3<f32> {
+        let world_coords = self.faces[face].map(|f| self.vertexes[f.vertex_index]);
+        cross(
+            diff(world_coords[1], world_coords[0]),
+            diff(world_coords[2], world_coords[0]),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wireframe::write_test_file;
+
+    #[test]
+    fn test_render_frame_shared_edges() {
+        let vertexes = "v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\n";
+        let square = write_test_file("square.obj", format!("{}f 1 2 3\nf 1 3 4\n", vertexes));
+        let triangle = write_test_file("triangle.obj", format!("{}f 1 2 3\n", vertexes));
+        for style in [
+            LineStyle::Aliased,
+            LineStyle::AntiAliased,
+            LineStyle::Thick(2.0),
+        ] {
+            let render = |path: &String| {
+                let mut image = RGBImage::new(16, 9, BLACK_COLOR);
+                let model = WireframeModel::from_file(path.clone()).unwrap();
+                image.render_frame(model, WHITE_COLOR, style);
+                image
+            };
+            let (square, triangle) = (render(&square), render(&triangle));
+            // the diagonal far from other edges looks the same as when it's drawn once
+            for y in 3..6 {
+                for x in 3..13 {
+                    let i = x + y * 16;
+                    assert_eq!(square.pixels[i].g, triangle.pixels[i].g, "{:?}", style);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_render_transparent() {
+        write_test_file(
+            "transparent/glass.mtl",
+            "newmtl red\nKd 1 0 0\nd 0.5\nnewmtl blue\nKd 0 0 1\nd 0.5\n",
+        );
+        // the front face comes first, so it's only right if faces are sorted
+        let path = write_test_file(
+            "transparent/glass.obj",
+            "mtllib glass.mtl\n\
+             v -1 -1 0.5\nv 1 -1 0.5\nv 1 1 0.5\nv -1 1 0.5\n\
+             v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\n\
+             usemtl blue\nf 1 2 3 4\nusemtl red\nf 5 6 7 8\n",
+        );
+        let model = WireframeModel::from_file(path).unwrap();
+        let clear = RGBAColor {
+            r: 0,
+            g: 0,
+            b: 0,
+            a: 0,
+        };
+        let mut image = RGBAImage::new(32, 32, clear);
+        let texture = Texture::from(image::DynamicImage::new_rgb8(1, 1));
+        // no lights, the ambient term keeps material colors as they are
+        let phong = Phong {
+            ambient: 1.0,
+            diffuse: 0.0,
+            specular: 0.0,
+            ..Phong::default()
+        };
+        let camera = Camera {
+            position: Vec3 {
+                x: 0.0,
+                y: 0.0,
+                z: 3.0,
+            },
+            ..Camera::default()
+        };
+        image.render_transparent(model, texture, phong, &[], &camera, Blending::AlphaOver);
+
+        let color = |r, b| RGBAColor { r, g: 0, b, a: 127 };
+        let red_behind = Blending::AlphaOver.blend(color(255, 0), clear);
+        let expected = Blending::AlphaOver.blend(color(0, 255), red_behind);
+        // off the diagonals, which are shared by two triangles of each quad
+        assert_eq!(image.pixels[20 + 12 * 32], expected);
+        assert_ne!(
+            expected,
+            Blending::AlphaOver.blend(red_behind, color(0, 255))
+        );
+    }
+
+    #[test]
+    fn test_shadow_map_light() {
+        // small quad hanging above a large floor
+        let path = write_test_file(
+            "shadow.obj",
+            "v -1 0 -1\nv 1 0 -1\nv 1 0 1\nv -1 0 1\n\
+             v -0.3 0.5 -0.3\nv 0.3 0.5 -0.3\nv 0.3 0.5 0.3\nv -0.3 0.5 0.3\n\
+             f 1 2 3 4\nf 5 6 7 8\n",
+        );
+        let model = WireframeModel::from_file(path).unwrap();
+        let canvas = RGBImage::new(64, 64, BLACK_COLOR);
+        let light_dir = Vec3 {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        };
+        let shadow_map = canvas.shadow_map(&model, light_dir, Shadow::default());
+        let floor = |x: f32, z: f32| shadow_map.light(Vec3 { x, y: 0.0, z });
+
+        assert_eq!(floor(0.0, 0.0), 0.0);
+        assert_eq!(floor(0.1, -0.2), 0.0);
+        assert_eq!(floor(0.8, 0.8), 1.0);
+        assert_eq!(floor(-0.6, 0.0), 1.0);
+        let penumbra = floor(0.3, 0.0);
+        assert!(penumbra > 0.0 && penumbra < 1.0, "{}", penumbra);
+        // the occluder itself is lit
+        assert_eq!(
+            shadow_map.light(Vec3 {
+                x: 0.0,
+                y: 0.5,
+                z: 0.0
+            }),
+            1.0
+        );
     }
 }
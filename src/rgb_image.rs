@@ -1,4 +1,4 @@
-use crate::point::Point;
+use crate::point::{Point, Vec3};
 
 #[repr(C, packed)]
 #[derive(Clone, Copy)]
@@ -40,6 +40,45 @@ impl RGBColor {
             r: (self.r as f32 * i) as u8,
         }
     }
+
+    // Distinct colors for enumerated items, hue is rotated by the golden angle
+    pub(crate) fn indexed(i: usize) -> RGBColor {
+        let hue = (i as f32 * 137.508) % 360.0 / 60.0;
+        let x = 1.0 - (hue % 2.0 - 1.0).abs();
+        let (r, g, b) = match hue as u32 {
+            0 => (1.0, x, 0.0),
+            1 => (x, 1.0, 0.0),
+            2 => (0.0, 1.0, x),
+            3 => (0.0, x, 1.0),
+            4 => (x, 0.0, 1.0),
+            _ => (1.0, 0.0, x),
+        };
+        let channel = |v: f32| (55.0 + v * 200.0) as u8;
+        RGBColor {
+            b: channel(b),
+            g: channel(g),
+            r: channel(r),
+        }
+    }
+
+    // `color` is a linear filter with `r`, `g`, `b` as `x`, `y`, `z`
+    pub(crate) fn tinted(self, color: Vec3<f32>) -> RGBColor {
+        RGBColor {
+            b: (self.b as f32 * color.z) as u8,
+            g: (self.g as f32 * color.y) as u8,
+            r: (self.r as f32 * color.x) as u8,
+        }
+    }
+
+    // Color multiplied by `diffuse` light with added `specular` highlight, both in 0..1 range
+    pub(crate) fn shaded(self, diffuse: Vec3<f32>, specular: Vec3<f32>) -> RGBColor {
+        let channel = |c: u8, d: f32, s: f32| (c as f32 * d + 255.0 * s).min(255.0) as u8;
+        RGBColor {
+            b: channel(self.b, diffuse.z, specular.z),
+            g: channel(self.g, diffuse.y, specular.y),
+            r: channel(self.r, diffuse.x, specular.x),
+        }
+    }
 }
 
 // Rust standard library doesn't have any built-in pseudo random generator :facepalm:
@@ -76,6 +115,21 @@ impl RGBImage {
         self.pixels[usize::from(point.x) + usize::from(point.y) * usize::from(self.width)] = color;
     }
 
+    // Mixes `color` into the pixel by `coverage` in 0..1 range, pixels outside of the image are skipped
+    pub(crate) fn blend_pixel(&mut self, x: i32, y: i32, color: RGBColor, coverage: f32) {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 || coverage <= 0.0 {
+            return;
+        }
+        let coverage = coverage.min(1.0);
+        let pixel = &mut self.pixels[x as usize + y as usize * usize::from(self.width)];
+        let mix = |p: u8, c: u8| (p as f32 + (c as f32 - p as f32) * coverage).round() as u8;
+        *pixel = RGBColor {
+            b: mix(pixel.b, color.b),
+            g: mix(pixel.g, color.g),
+            r: mix(pixel.r, color.r),
+        };
+    }
+
     pub fn flip_vertically(&mut self) {
         for y in 0..self.height / 2 {
             for x in 0..self.width {
@@ -0,0 +1,189 @@
+use crate::rgb_image::{RGBColor, RGBImage};
+use crate::shader::{DepthBuffer, Shader};
+use crate::wireframe::WireframeModel;
+
+// Alpha compositing
+// https://en.wikipedia.org/wiki/Alpha_compositing
+// https://en.wikipedia.org/wiki/Blend_modes
+
+/// Color with straight (not premultiplied) alpha, 255 is fully opaque.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct RGBAColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl RGBAColor {
+    pub fn rgb(self) -> RGBColor {
+        RGBColor {
+            b: self.b,
+            g: self.g,
+            r: self.r,
+        }
+    }
+}
+
+impl From<RGBColor> for RGBAColor {
+    fn from(color: RGBColor) -> Self {
+        RGBAColor {
+            r: color.r,
+            g: color.g,
+            b: color.b,
+            a: 255,
+        }
+    }
+}
+
+impl RGBColor {
+    pub(crate) fn with_alpha(self, a: u8) -> RGBAColor {
+        RGBAColor {
+            a,
+            ..RGBAColor::from(self)
+        }
+    }
+}
+
+/// How a fragment color is combined with the pixel under it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Blending {
+    /// The fragment replaces the pixel
+    Replace,
+    /// The fragment is composited over the pixel by its alpha
+    AlphaOver,
+    /// The fragment color weighted by its alpha is added to the pixel
+    Additive,
+    /// The pixel is multiplied by the fragment color weighted by its alpha
+    Multiply,
+}
+
+impl Blending {
+    /// Combines `source` (fragment) color with `destination` (pixel) one.
+    pub fn blend(self, source: RGBAColor, destination: RGBAColor) -> RGBAColor {
+        let sa = source.a as f32 / 255.0;
+        let da = destination.a as f32 / 255.0;
+        let byte = |v: f32| v.round().clamp(0.0, 255.0) as u8;
+        let mix = |f: &dyn Fn(f32, f32) -> f32, a: f32| RGBAColor {
+            r: byte(f(source.r as f32, destination.r as f32)),
+            g: byte(f(source.g as f32, destination.g as f32)),
+            b: byte(f(source.b as f32, destination.b as f32)),
+            a: byte(a * 255.0),
+        };
+        match self {
+            Blending::Replace => source,
+            Blending::AlphaOver => {
+                let a = sa + da * (1.0 - sa);
+                if a == 0.0 {
+                    return RGBAColor {
+                        a: 0,
+                        ..destination
+                    };
+                }
+                mix(&|s, d| (s * sa + d * da * (1.0 - sa)) / a, a)
+            }
+            Blending::Additive => mix(&|s, d| d + s * sa, (da + sa).min(1.0)),
+            Blending::Multiply => mix(&|s, d| d * (1.0 - sa + s / 255.0 * sa), da),
+        }
+    }
+}
+
+pub struct RGBAImage {
+    pub pixels: Vec<RGBAColor>,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl RGBAImage {
+    pub fn new(width: u16, height: u16, color: RGBAColor) -> Self {
+        RGBAImage {
+            pixels: vec![color; usize::from(width) * usize::from(height)],
+            width,
+            height,
+        }
+    }
+
+    /// Composites the image over an opaque `background`.
+    pub fn to_rgb(&self, background: RGBColor) -> RGBImage {
+        let background = RGBAColor::from(background);
+        let pixels = self
+            .pixels
+            .iter()
+            .map(|&c| Blending::AlphaOver.blend(c, background).rgb())
+            .collect();
+        RGBImage {
+            pixels,
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    /// Blends `Shader::fragment_rgba` colors of the given faces into the image.
+    /// Faces are tested against `depth` but only opaque fragments write it,
+    /// so transparent faces should be drawn after opaque ones in back to front order
+    /// (see `Camera::back_to_front`).
+    pub fn draw_faces(
+        &mut self,
+        model: &WireframeModel,
+        faces: impl IntoIterator<Item = usize>,
+        shader: &mut dyn Shader,
+        blending: Blending,
+        depth: &mut DepthBuffer,
+    ) {
+        assert_eq!((depth.width, depth.height), (self.width, self.height));
+        let width = usize::from(self.width);
+        depth.rasterize(
+            model,
+            faces,
+            shader,
+            &mut |shader, p, bc| match shader.fragment_rgba(bc) {
+                Some(color) => {
+                    let pixel = &mut self.pixels[usize::from(p.x) + usize::from(p.y) * width];
+                    *pixel = blending.blend(color, *pixel);
+                    color.a == 255
+                }
+                None => false,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn color(r: u8, g: u8, b: u8, a: u8) -> RGBAColor {
+        RGBAColor { r, g, b, a }
+    }
+
+    #[test]
+    fn test_blending() {
+        let glass = color(255, 0, 0, 128);
+        let wall = color(0, 0, 200, 255);
+        assert_eq!(Blending::Replace.blend(glass, wall), glass);
+        assert_eq!(
+            Blending::AlphaOver.blend(glass, wall),
+            color(128, 0, 100, 255)
+        );
+        assert_eq!(Blending::AlphaOver.blend(color(0, 0, 0, 0), wall), wall);
+        assert_eq!(Blending::AlphaOver.blend(glass, color(0, 0, 0, 0)), glass);
+        assert_eq!(
+            Blending::Additive.blend(glass, wall),
+            color(128, 0, 200, 255)
+        );
+        assert_eq!(
+            Blending::Multiply.blend(color(128, 255, 0, 255), color(200, 200, 200, 255)),
+            color(100, 200, 0, 255)
+        );
+    }
+
+    #[test]
+    fn test_to_rgb() {
+        let mut image = RGBAImage::new(2, 1, color(0, 0, 0, 0));
+        image.pixels[1] = color(255, 255, 255, 51);
+        let rgb = image.to_rgb(RGBColor { r: 0, g: 100, b: 0 });
+        assert_eq!(image.pixels[0].rgb().g, 0);
+        assert_eq!(rgb.pixels[0].g, 100);
+        assert_eq!((rgb.pixels[1].r, rgb.pixels[1].g), (51, 131));
+    }
+}
@@ -0,0 +1,266 @@
+use crate::clipping::clip_triangle;
+use crate::matrix::ViewPort;
+use crate::point::{Point, Vec3, Vec4};
+use crate::rgb_image::{RGBColor, RGBImage, BLACK_COLOR};
+use crate::rgba_image::RGBAColor;
+use crate::wireframe::WireframeModel;
+
+/// Programmable stages of the rendering pipeline driven by `draw` of `RGBImage`, `HDRImage`
+/// and `RGBAImage`.
+pub trait Shader {
+    /// Transforms `nth_vertex` (0..3) of face `face` of the `model` into homogeneous clip
+    /// coordinates, visible points are inside `-w <= x, y, z <= w` and greater `z / w` is closer.
+    /// Anything `fragment` needs later (varyings) should be stored in the shader itself.
+    fn vertex(&mut self, model: &WireframeModel, face: usize, nth_vertex: usize) -> Vec4<f32>;
+
+    /// Called before fragments of the last transformed face if it's visible with its clip
+    /// coordinates mapped to the image, the pixel is at `x / w`, `y / w`.
+    /// Shaders sampling mip mapped textures keep them for screen space derivatives.
+    fn window_coords(&mut self, _coords: [Vec4<f32>; 3]) {}
+
+    /// Returns the color of a pixel with barycentric coordinates `bc` inside the last
+    /// transformed face or `None` to discard it. Coordinates are perspective correct
+    /// and relative to the original corners even if the face is clipped.
+    fn fragment(&self, bc: Vec3<f32>) -> Option<RGBColor>;
+
+    /// Same as `fragment` for HDR targets, linear `r`, `g`, `b` as `x`, `y`, `z` which may exceed 1.
+    /// By default it's the `fragment` color decoded from sRGB.
+    fn fragment_hdr(&self, bc: Vec3<f32>) -> Option<Vec3<f32>> {
+        self.fragment(bc).map(RGBColor::to_linear)
+    }
+
+    /// Same as `fragment` with alpha for `RGBAImage`, opaque by default.
+    fn fragment_rgba(&self, bc: Vec3<f32>) -> Option<RGBAColor> {
+        self.fragment(bc).map(RGBAColor::from)
+    }
+}
+
+/// Depth of the closest drawn fragment for every pixel, -1 where nothing was drawn.
+pub struct DepthBuffer {
+    pub depth: Vec<f32>,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl DepthBuffer {
+    pub fn new(width: u16, height: u16) -> Self {
+        DepthBuffer {
+            depth: vec![-1.0; usize::from(width) * usize::from(height)],
+            width,
+            height,
+        }
+    }
+
+    /// Depth at the pixel, `None` outside of the buffer.
+    pub fn get(&self, x: i32, y: i32) -> Option<f32> {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return None;
+        }
+        Some(self.depth[x as usize + y as usize * usize::from(self.width)])
+    }
+
+    /// Grayscale image for debugging, the closest pixels are white.
+    pub fn to_image(&self) -> RGBImage {
+        let drawn = self.depth.iter().filter(|&&d| d >= 0.0);
+        let max = drawn.fold(0.0_f32, |max, &d| max.max(d));
+        let pixels = self
+            .depth
+            .iter()
+            .map(|&d| {
+                if d >= 0.0 && max > 0.0 {
+                    RGBColor::intensity(d / max)
+                } else {
+                    BLACK_COLOR
+                }
+            })
+            .collect();
+        RGBImage {
+            pixels,
+            width: self.width,
+            height: self.height,
+        }
+    }
+}
+
+// Writes a pixel of the face drawn with the shader, returns whether the depth is written
+type Plot<'a> = dyn FnMut(&dyn Shader, Point, Vec3<f32>) -> bool + 'a;
+
+impl DepthBuffer {
+    // Clips faces by the view frustum, maps them to the whole buffer and rasterizes them,
+    // `plot` gets pixels passing the depth test with barycentric coordinates in the original
+    // face and returns `false` to keep the depth as is (discarded or transparent pixels)
+    pub(crate) fn rasterize(
+        &mut self,
+        model: &WireframeModel,
+        faces: impl IntoIterator<Item = usize>,
+        shader: &mut dyn Shader,
+        plot: &mut Plot,
+    ) {
+        let view_port = ViewPort {
+            x: 0,
+            y: 0,
+            width: self.width,
+            height: self.height,
+        }
+        .to_matrix();
+
+        for face in faces {
+            let clip_coords = [0, 1, 2].map(|nth_vertex| shader.vertex(model, face, nth_vertex));
+            let polygon = clip_triangle(clip_coords);
+            if polygon.is_empty() {
+                continue;
+            }
+            shader.window_coords(clip_coords.map(|p| view_port.transform_vec4(p)));
+            let screen_coords: Vec<Vec3<f32>> = polygon
+                .iter()
+                .map(|v| {
+                    let p = v.position;
+                    let ndc = Vec3 {
+                        x: p.x / p.w,
+                        y: p.y / p.w,
+                        z: p.z / p.w,
+                    };
+                    view_port.transform(ndc)
+                })
+                .collect();
+            // the clipped polygon is convex, so it's split into a triangle fan
+            for i in 1..polygon.len().saturating_sub(1) {
+                let corners = [0, i, i + 1];
+                let pts = corners.map(|c| screen_coords[c]);
+                let inverse_w = corners.map(|c| 1.0 / polygon[c].position.w);
+                let bcs = corners.map(|c| polygon[c].bc);
+                let shader: &dyn Shader = shader;
+                self.triangle(pts, inverse_w, &mut |p, bc| {
+                    let bc = Vec3 {
+                        x: bcs[0].x * bc.x + bcs[1].x * bc.y + bcs[2].x * bc.z,
+                        y: bcs[0].y * bc.x + bcs[1].y * bc.y + bcs[2].y * bc.z,
+                        z: bcs[0].z * bc.x + bcs[1].z * bc.y + bcs[2].z * bc.z,
+                    };
+                    plot(shader, p, bc)
+                });
+            }
+        }
+    }
+}
+
+impl RGBImage {
+    /// Draws all faces except ones from hidden groups.
+    pub fn draw(&mut self, model: &WireframeModel, shader: &mut dyn Shader) -> DepthBuffer {
+        self.draw_faces(model, model.visible_faces(), shader)
+    }
+
+    /// Draws only the given faces, e.g. `group.faces` of a single group.
+    /// Faces are clipped by the view frustum and mapped to the whole image,
+    /// depth goes from 0 (far) to 255 (near).
+    pub fn draw_faces(
+        &mut self,
+        model: &WireframeModel,
+        faces: impl IntoIterator<Item = usize>,
+        shader: &mut dyn Shader,
+    ) -> DepthBuffer {
+        let mut z_buffer = DepthBuffer::new(self.width, self.height);
+        z_buffer.rasterize(
+            model,
+            faces,
+            shader,
+            &mut |shader, p, bc| match shader.fragment(bc) {
+                Some(color) => {
+                    self.set_pixel(p, color);
+                    true
+                }
+                None => false,
+            },
+        );
+        z_buffer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rgb_image::RED_COLOR;
+    use crate::wireframe::Face;
+
+    struct SolidShader {
+        clip_coords: [Vec4<f32>; 3],
+    }
+
+    impl Shader for SolidShader {
+        fn vertex(
+            &mut self,
+            _model: &WireframeModel,
+            _face: usize,
+            nth_vertex: usize,
+        ) -> Vec4<f32> {
+            self.clip_coords[nth_vertex]
+        }
+
+        fn fragment(&self, bc: Vec3<f32>) -> Option<RGBColor> {
+            // keep only the half of the triangle near the first vertex
+            if bc.x > 0.5 {
+                Some(RED_COLOR)
+            } else {
+                None
+            }
+        }
+    }
+
+    fn triangle_model() -> WireframeModel {
+        let face = Face {
+            vertex_index: 0,
+            texture_index: None,
+            norm_index: None,
+        };
+        WireframeModel {
+            faces: vec![[face; 3]],
+            polygons: vec![vec![face; 3]],
+            face_materials: vec![None],
+            ..Default::default()
+        }
+    }
+
+    fn point(x: f32, y: f32) -> Vec4<f32> {
+        Vec4 {
+            x,
+            y,
+            z: 1.0,
+            w: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_draw_discards_fragments() {
+        let mut shader = SolidShader {
+            // (0, 0), (9, 0) and (0, 9) in pixels
+            clip_coords: [point(-1.0, -1.0), point(0.8, -1.0), point(-1.0, 0.8)],
+        };
+        let mut image = RGBImage::new(10, 10, BLACK_COLOR);
+        let depth = image.draw(&triangle_model(), &mut shader);
+
+        let red = |x: usize, y: usize| image.pixels[x + y * 10].r == 255;
+        assert!(red(0, 0));
+        assert!(red(2, 1));
+        assert!(!red(8, 0));
+        assert!(!red(9, 9));
+        assert!((depth.get(0, 0).unwrap() - 255.0).abs() < 1e-3);
+        assert_eq!(depth.get(9, 9), Some(-1.0));
+        assert_eq!(depth.get(10, 0), None);
+    }
+
+    #[test]
+    fn test_draw_clips_faces() {
+        let mut shader = SolidShader {
+            clip_coords: [point(-2.0, -2.0), point(6.0, -2.0), point(-2.0, 6.0)],
+        };
+        let mut image = RGBImage::new(10, 10, BLACK_COLOR);
+        let depth = image.draw(&triangle_model(), &mut shader);
+
+        let red = |x: usize, y: usize| image.pixels[x + y * 10].r == 255;
+        // barycentric coordinates are relative to the original triangle
+        assert!(red(0, 0));
+        assert!(red(8, 0));
+        assert!(red(0, 8));
+        assert!(!red(9, 9));
+        assert!((depth.get(8, 0).unwrap() - 255.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,245 @@
+use crate::loader::MeshError;
+use crate::point::Vec3;
+use crate::wireframe::{Face, Group, Vertex3, WireframeModel};
+use std::collections::HashMap;
+use std::fs;
+
+// Stereolithography triangle soup, ascii and binary variants
+// https://www.fabbers.com/tech/STL_Format
+
+// STL has no shared vertices, equal positions are merged to keep the model connected
+struct Builder {
+    model: WireframeModel,
+    indices: HashMap<[u32; 3], usize>,
+}
+
+impl Builder {
+    fn new() -> Self {
+        Builder {
+            model: WireframeModel::default(),
+            indices: HashMap::new(),
+        }
+    }
+
+    fn vertex(&mut self, v: Vertex3) -> usize {
+        let vertexes = &mut self.model.vertexes;
+        *self
+            .indices
+            .entry([v.x.to_bits(), v.y.to_bits(), v.z.to_bits()])
+            .or_insert_with(|| {
+                vertexes.push(v);
+                vertexes.len() - 1
+            })
+    }
+
+    fn facet(&mut self, normal: Vec3<f32>, points: [Vertex3; 3]) {
+        // exporters often write zero normals, renderers compute them from the triangle then
+        let norm_index = if normal == Vec3::default() {
+            None
+        } else {
+            self.model.norm.push(normal);
+            Some(self.model.norm.len() - 1)
+        };
+        let face = points.map(|p| Face {
+            vertex_index: self.vertex(p),
+            texture_index: None,
+            norm_index,
+        });
+        self.model.faces.push(face);
+        self.model.polygons.push(face.to_vec());
+        self.model.face_materials.push(None);
+    }
+
+    fn group(&mut self, name: &str) {
+        self.end_group();
+        let model = &mut self.model;
+        model.groups.push(Group::new(
+            name.to_string(),
+            model.faces.len(),
+            model.polygons.len(),
+        ));
+    }
+
+    fn end_group(&mut self) {
+        let (faces, polygons) = (self.model.faces.len(), self.model.polygons.len());
+        if let Some(group) = self.model.groups.last_mut() {
+            group.end(faces, polygons);
+        }
+    }
+
+    fn build(mut self) -> WireframeModel {
+        self.end_group();
+        self.model
+    }
+}
+
+// 80 bytes header, triangles count and 50 bytes per triangle
+fn is_binary(data: &[u8]) -> bool {
+    match data.get(80..84) {
+        Some(b) => {
+            let count = u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize;
+            data.len() == 84 + count * 50
+        }
+        None => false,
+    }
+}
+
+fn parse_binary(data: &[u8]) -> WireframeModel {
+    let f32_at = |offset: usize| {
+        f32::from_le_bytes([
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        ])
+    };
+    let vec3_at = |offset: usize| Vec3 {
+        x: f32_at(offset),
+        y: f32_at(offset + 4),
+        z: f32_at(offset + 8),
+    };
+    let mut builder = Builder::new();
+    builder.group("default");
+    for offset in (84..data.len()).step_by(50) {
+        // normal, three vertices and two bytes of attributes
+        let points = [
+            vec3_at(offset + 12),
+            vec3_at(offset + 24),
+            vec3_at(offset + 36),
+        ];
+        builder.facet(vec3_at(offset), points);
+    }
+    builder.build()
+}
+
+fn parse_ascii(text: &str) -> Result<WireframeModel, String> {
+    let mut builder = Builder::new();
+    let mut normal = Vec3::default();
+    let mut points: Vec<Vertex3> = vec![];
+    for (n, line) in text.lines().enumerate() {
+        let error = |message: String| format!("line {}: {}", n + 1, message);
+        let vector = |fields: &str| -> Result<Vec3<f32>, String> {
+            let values: Vec<f32> = fields
+                .split_ascii_whitespace()
+                .map(|v| v.parse::<f32>())
+                .collect::<Result<_, _>>()
+                .map_err(|_| error(format!("expected three numbers, found `{}`", fields)))?;
+            match values[..] {
+                [x, y, z] => Ok(Vec3 { x, y, z }),
+                _ => Err(error(format!("expected three numbers, found `{}`", fields))),
+            }
+        };
+        let line = line.trim();
+        let (keyword, fields) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        match keyword {
+            "solid" => builder.group(fields.trim()),
+            "facet" => {
+                let fields = fields.trim_start();
+                let fields = fields
+                    .strip_prefix("normal")
+                    .ok_or_else(|| error(format!("expected `normal`, found `{}`", fields)))?;
+                normal = vector(fields)?;
+                points.clear();
+            }
+            "vertex" => points.push(vector(fields)?),
+            "endfacet" => match points[..] {
+                [a, b, c] => builder.facet(normal, [a, b, c]),
+                _ => {
+                    return Err(error(format!(
+                        "expected 3 vertices, found {}",
+                        points.len()
+                    )))
+                }
+            },
+            "endsolid" => builder.end_group(),
+            "outer" | "endloop" | "" => {}
+            _ => return Err(error(format!("unexpected `{}`", keyword))),
+        }
+    }
+    if builder.model.groups.is_empty() {
+        return Err("expected `solid`".to_string());
+    }
+    Ok(builder.build())
+}
+
+impl WireframeModel {
+    /// Loads ascii or binary STL file, every `solid` becomes a group.
+    pub fn from_stl(path: String) -> Result<WireframeModel, MeshError> {
+        let data = fs::read(&path).map_err(|error| MeshError::Io {
+            path: path.clone(),
+            error,
+        })?;
+        let format_error = |message: String| MeshError::Format {
+            path: path.clone(),
+            message,
+        };
+        // binary files may start with `solid` too, so the size is checked first
+        if is_binary(&data) {
+            return Ok(parse_binary(&data));
+        }
+        let text = std::str::from_utf8(&data)
+            .map_err(|_| format_error("invalid binary stl size".to_string()))?;
+        parse_ascii(text).map_err(format_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ascii() {
+        let model = parse_ascii(
+            "solid quad\n\
+             facet normal 0 0 1\n outer loop\n  vertex 0 0 0\n  vertex 1 0 0\n  vertex 1 1 0\n endloop\nendfacet\n\
+             facet normal 0 0 0\n outer loop\n  vertex 0 0 0\n  vertex 1 1 0\n  vertex 0 1 0\n endloop\nendfacet\n\
+             endsolid quad\n",
+        )
+        .unwrap();
+        assert_eq!(model.vertexes.len(), 4);
+        assert_eq!(model.faces.len(), 2);
+        assert_eq!(
+            model.norm,
+            [Vec3 {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0
+            }]
+        );
+        assert_eq!(model.faces[0][2].norm_index, Some(0));
+        assert_eq!(model.faces[1][0].norm_index, None);
+        assert_eq!(model.faces[1][1].vertex_index, 2);
+        assert_eq!(model.groups.len(), 1);
+        assert_eq!(model.groups[0].name, "quad");
+        assert_eq!(model.groups[0].face_indices().collect::<Vec<_>>(), [0, 1]);
+
+        let error = parse_ascii("solid\nfacet normal 0 0\n").err().unwrap();
+        assert_eq!(error, "line 2: expected three numbers, found ` 0 0`");
+    }
+
+    #[test]
+    fn test_binary() {
+        let mut data = b"solid binary files may start with solid".to_vec();
+        data.resize(80, 0);
+        data.extend_from_slice(&1u32.to_le_bytes());
+        for v in [
+            0.0f32, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0,
+        ] {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        data.extend_from_slice(&[0, 0]);
+        assert!(is_binary(&data));
+
+        let model = parse_binary(&data);
+        assert_eq!(
+            model.vertexes[1],
+            Vec3 {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0
+            }
+        );
+        assert_eq!(model.faces.len(), 1);
+        assert_eq!(model.groups[0].face_indices().collect::<Vec<_>>(), [0]);
+    }
+}
@@ -0,0 +1,354 @@
+use crate::point::{cross, dot_product, Vec2, Vec3, Vec4};
+use crate::rgba_image::{RGBAColor, RGBAImage};
+use image::{DynamicImage, GenericImageView};
+
+// Texture filtering and mipmapping
+// https://en.wikipedia.org/wiki/Texture_filtering
+// https://en.wikipedia.org/wiki/Mipmap
+
+/// How texels around the sampled point are combined.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Filter {
+    /// The closest texel
+    Nearest,
+    /// 4 closest texels weighted by the distance
+    Bilinear,
+    /// Bilinear samples of two mip levels closest to the pixel footprint blended together
+    Trilinear,
+}
+
+/// How texture coordinates outside of 0..1 are mapped to the texture.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Wrap {
+    /// The texture is tiled
+    Repeat,
+    /// Edge texels are stretched
+    Clamp,
+    /// The texture is tiled, every other tile is flipped
+    Mirror,
+}
+
+impl Wrap {
+    fn texel(self, i: i64, size: u16) -> usize {
+        let size = i64::from(size);
+        let i = match self {
+            Wrap::Repeat => i.rem_euclid(size),
+            Wrap::Clamp => i.clamp(0, size - 1),
+            Wrap::Mirror => {
+                let i = i.rem_euclid(2 * size);
+                if i < size {
+                    i
+                } else {
+                    2 * size - 1 - i
+                }
+            }
+        };
+        i as usize
+    }
+}
+
+/// Image with its mip chain, every level is half of the previous one down to 1x1.
+/// Texture coordinates are 0..1 over the whole image, `v` goes down the rows.
+pub struct Texture {
+    levels: Vec<RGBAImage>,
+    pub filter: Filter,
+    pub wrap: Wrap,
+}
+
+type Texel = [f32; 4];
+
+fn mix(a: Texel, b: Texel, t: f32) -> Texel {
+    [0, 1, 2, 3].map(|i| a[i] + (b[i] - a[i]) * t)
+}
+
+impl Texture {
+    pub fn new(image: &DynamicImage, filter: Filter, wrap: Wrap) -> Self {
+        let (width, height) = image.dimensions();
+        let mut level = RGBAImage::new(width as u16, height as u16, RGBAColor::default());
+        for (x, y, pixel) in image.pixels() {
+            let [r, g, b, a] = pixel.0;
+            level.pixels[(x + y * width) as usize] = RGBAColor { r, g, b, a };
+        }
+        let mut levels = vec![level];
+        while let Some(level) = levels.last().and_then(Texture::half) {
+            levels.push(level);
+        }
+        Texture {
+            levels,
+            filter,
+            wrap,
+        }
+    }
+
+    pub fn width(&self) -> u16 {
+        self.levels[0].width
+    }
+
+    pub fn height(&self) -> u16 {
+        self.levels[0].height
+    }
+
+    // Next mip level, 2x2 texels are averaged, the last row or column of odd sizes is dropped
+    fn half(level: &RGBAImage) -> Option<RGBAImage> {
+        if level.width == 1 && level.height == 1 {
+            return None;
+        }
+        let (width, height) = ((level.width / 2).max(1), (level.height / 2).max(1));
+        let texel = |x: u16, y: u16| {
+            let x = x.min(level.width - 1);
+            let y = y.min(level.height - 1);
+            let c = level.pixels[usize::from(x) + usize::from(y) * usize::from(level.width)];
+            [c.r, c.g, c.b, c.a].map(f32::from)
+        };
+        let mut half = RGBAImage::new(width, height, RGBAColor::default());
+        for y in 0..height {
+            for x in 0..width {
+                let top = mix(texel(2 * x, 2 * y), texel(2 * x + 1, 2 * y), 0.5);
+                let bottom = mix(texel(2 * x, 2 * y + 1), texel(2 * x + 1, 2 * y + 1), 0.5);
+                half.pixels[usize::from(x) + usize::from(y) * usize::from(width)] =
+                    Texture::color(mix(top, bottom, 0.5));
+            }
+        }
+        Some(half)
+    }
+
+    fn color(texel: Texel) -> RGBAColor {
+        let [r, g, b, a] = texel.map(|c| c.round().clamp(0.0, 255.0) as u8);
+        RGBAColor { r, g, b, a }
+    }
+
+    fn texel(&self, level: &RGBAImage, x: i64, y: i64) -> Texel {
+        let x = self.wrap.texel(x, level.width);
+        let y = self.wrap.texel(y, level.height);
+        let c = level.pixels[x + y * usize::from(level.width)];
+        [c.r, c.g, c.b, c.a].map(f32::from)
+    }
+
+    fn nearest(&self, level: &RGBAImage, uv: Vec2<f32>) -> Texel {
+        let x = (uv.x * level.width as f32).floor() as i64;
+        let y = (uv.y * level.height as f32).floor() as i64;
+        self.texel(level, x, y)
+    }
+
+    fn bilinear(&self, level: &RGBAImage, uv: Vec2<f32>) -> Texel {
+        // texel centers are at half integer coordinates
+        let x = uv.x * level.width as f32 - 0.5;
+        let y = uv.y * level.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let (x1, y1) = (x0.saturating_add(1), y0.saturating_add(1));
+        let top = mix(self.texel(level, x0, y0), self.texel(level, x1, y0), tx);
+        let bottom = mix(self.texel(level, x0, y1), self.texel(level, x1, y1), tx);
+        mix(top, bottom, ty)
+    }
+
+    /// Mip level for the pixel footprint given by screen space derivatives of texture
+    /// coordinates, 0 is the full size image.
+    pub fn lod(&self, duv_dx: Vec2<f32>, duv_dy: Vec2<f32>) -> f32 {
+        let texels = |d: Vec2<f32>| {
+            let x = d.x * self.width() as f32;
+            let y = d.y * self.height() as f32;
+            (x * x + y * y).sqrt()
+        };
+        // NaN for degenerate faces becomes 0 as well
+        texels(duv_dx).max(texels(duv_dy)).log2().max(0.0)
+    }
+
+    /// Color at texture coordinates `uv`, `lod` is only used by `Filter::Trilinear`.
+    pub fn sample(&self, uv: Vec2<f32>, lod: f32) -> RGBAColor {
+        match self.filter {
+            Filter::Nearest => Texture::color(self.nearest(&self.levels[0], uv)),
+            Filter::Bilinear => Texture::color(self.bilinear(&self.levels[0], uv)),
+            Filter::Trilinear => {
+                let lod = lod.max(0.0).min((self.levels.len() - 1) as f32);
+                let level = lod.floor() as usize;
+                let texel = self.bilinear(&self.levels[level], uv);
+                let t = lod - level as f32;
+                if t > 0.0 {
+                    let next = self.bilinear(&self.levels[level + 1], uv);
+                    Texture::color(mix(texel, next, t))
+                } else {
+                    Texture::color(texel)
+                }
+            }
+        }
+    }
+}
+
+impl From<DynamicImage> for Texture {
+    /// Trilinear filtering of the repeated image.
+    fn from(image: DynamicImage) -> Self {
+        Texture::new(&image, Filter::Trilinear, Wrap::Repeat)
+    }
+}
+
+/// Screen space derivatives of texture coordinates `uv` of the face corners interpolated with
+/// perspective correct barycentric coordinates `bc`, `window` is given by `Shader::window_coords`.
+pub(crate) fn uv_derivatives(
+    window: [Vec4<f32>; 3],
+    uv: [Vec2<f32>; 3],
+    bc: Vec3<f32>,
+) -> (Vec2<f32>, Vec2<f32>) {
+    // `window` corners are columns of the matrix mapping barycentric coordinates
+    // to the homogeneous pixel, its inverse gives them for the pixel up to a scale
+    let xs = Vec3 {
+        x: window[0].x,
+        y: window[1].x,
+        z: window[2].x,
+    };
+    let ys = Vec3 {
+        x: window[0].y,
+        y: window[1].y,
+        z: window[2].y,
+    };
+    let ws = Vec3 {
+        x: window[0].w,
+        y: window[1].w,
+        z: window[2].w,
+    };
+    let det = dot_product(xs, cross(ys, ws));
+    if det.abs() < f32::EPSILON {
+        return (Vec2::default(), Vec2::default());
+    }
+    // the scale making barycentric coordinates sum to 1 is interpolated `w`
+    let w = dot_product(bc, ws);
+    let values = [0, 1, 2].map(|i| [uv[i].x, uv[i].y]);
+    let uv = [0, 1].map(|c| values[0][c] * bc.x + values[1][c] * bc.y + values[2][c] * bc.z);
+    let derivative = |d: Vec3<f32>| {
+        let sum = d.x + d.y + d.z;
+        let [x, y] = [0, 1].map(|c| {
+            let weighted = values[0][c] * d.x + values[1][c] * d.y + values[2][c] * d.z;
+            w * (weighted - uv[c] * sum) / det
+        });
+        Vec2 { x, y }
+    };
+    (derivative(cross(ys, ws)), derivative(cross(ws, xs)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    // 4x2 texture with 0, 10, 20, 30 red in the first row and 40, 50, 60, 70 in the second one
+    fn texture(filter: Filter, wrap: Wrap) -> Texture {
+        let image = RgbaImage::from_fn(4, 2, |x, y| Rgba([(x * 10 + y * 40) as u8, 0, 0, 255]));
+        Texture::new(&DynamicImage::ImageRgba8(image), filter, wrap)
+    }
+
+    fn red(texture: &Texture, u: f32, v: f32) -> u8 {
+        texture.sample(Vec2 { x: u, y: v }, 0.0).r
+    }
+
+    #[test]
+    fn test_wrap() {
+        let repeat = texture(Filter::Nearest, Wrap::Repeat);
+        assert_eq!(red(&repeat, 0.3, 0.2), 10);
+        assert_eq!(red(&repeat, 1.3, -0.8), 10);
+        assert_eq!(red(&repeat, -0.1, 0.7), 70);
+        let clamp = texture(Filter::Nearest, Wrap::Clamp);
+        assert_eq!(red(&clamp, 5.0, -3.0), 30);
+        assert_eq!(red(&clamp, -1.0, 1.0), 40);
+        let mirror = texture(Filter::Bilinear, Wrap::Mirror);
+        assert_eq!(red(&mirror, 1.0, 0.25), 30);
+        let mirror = texture(Filter::Nearest, Wrap::Mirror);
+        assert_eq!(red(&mirror, 1.1, 0.2), 30);
+        assert_eq!(red(&mirror, -0.1, 0.2), 0);
+        assert_eq!(red(&mirror, 2.3, 0.2), 10);
+        // never panics
+        for uv in [f32::NAN, f32::INFINITY, -1e30, 1e30] {
+            for filter in [Filter::Nearest, Filter::Bilinear, Filter::Trilinear] {
+                for wrap in [Wrap::Repeat, Wrap::Clamp, Wrap::Mirror] {
+                    red(&texture(filter, wrap), uv, uv);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_filters() {
+        let bilinear = texture(Filter::Bilinear, Wrap::Clamp);
+        // texel centers
+        assert_eq!(red(&bilinear, 0.125, 0.25), 0);
+        assert_eq!(red(&bilinear, 0.375, 0.75), 50);
+        // between 4 texels
+        assert_eq!(red(&bilinear, 0.25, 0.5), 25);
+
+        let trilinear = texture(Filter::Trilinear, Wrap::Clamp);
+        assert_eq!(trilinear.levels.len(), 3);
+        assert_eq!(trilinear.levels[1].pixels[0].r, 25);
+        assert_eq!(trilinear.levels[2].pixels[0].r, 35);
+        let uv = Vec2 { x: 0.125, y: 0.25 };
+        assert_eq!(trilinear.sample(uv, 0.0).r, 0);
+        assert_eq!(trilinear.sample(uv, 2.0).r, 35);
+        assert_eq!(trilinear.sample(uv, 10.0).r, 35);
+        assert_eq!(trilinear.sample(uv, 1.5).r, 30);
+    }
+
+    #[test]
+    fn test_lod() {
+        let texture = texture(Filter::Trilinear, Wrap::Repeat);
+        let uv = |x: f32, y: f32| Vec2 { x, y };
+        // the pixel is a single texel or less
+        assert_eq!(texture.lod(uv(0.25, 0.0), uv(0.0, 0.1)), 0.0);
+        assert_eq!(texture.lod(uv(1.0, 0.0), uv(0.0, 0.5)), 2.0);
+        assert_eq!(texture.lod(uv(0.0, 0.0), uv(0.0, 0.0)), 0.0);
+    }
+
+    #[test]
+    fn test_uv_derivatives() {
+        let uv = [
+            Vec2 { x: 0.0, y: 0.0 },
+            Vec2 { x: 1.0, y: 0.0 },
+            Vec2 { x: 0.0, y: 1.0 },
+        ];
+        // 10x10 pixels face without perspective
+        let window = [(0.0, 0.0), (10.0, 0.0), (0.0, 10.0)].map(|(x, y)| Vec4 {
+            x,
+            y,
+            z: 0.0,
+            w: 1.0,
+        });
+        let bc = Vec3 {
+            x: 0.5,
+            y: 0.25,
+            z: 0.25,
+        };
+        let (dx, dy) = uv_derivatives(window, uv, bc);
+        assert!((dx.x - 0.1).abs() < 1e-6 && dx.y.abs() < 1e-6);
+        assert!(dy.x.abs() < 1e-6 && (dy.y - 0.1).abs() < 1e-6);
+
+        // the second corner is twice farther, compare with the finite difference
+        let window = [
+            window[0],
+            Vec4 {
+                x: 20.0,
+                y: 0.0,
+                z: 0.0,
+                w: 2.0,
+            },
+            window[2],
+        ];
+        let at = |x: f32, y: f32| {
+            // perspective correct barycentric coordinates of the pixel
+            let l = Vec3 {
+                x: 1.0 - x / 10.0 - y / 10.0,
+                y: x / 10.0 / 2.0,
+                z: y / 10.0,
+            };
+            let sum = l.x + l.y + l.z;
+            Vec3 {
+                x: l.x / sum,
+                y: l.y / sum,
+                z: l.z / sum,
+            }
+        };
+        let (dx, dy) = uv_derivatives(window, uv, at(2.0, 3.0));
+        let h = 1e-3;
+        let (x0, x1) = (at(2.0, 3.0), at(2.0 + h, 3.0));
+        assert!((dx.x - (x1.y - x0.y) / h).abs() < 1e-3);
+        let y1 = at(2.0, 3.0 + h);
+        assert!((dy.y - (y1.z - x0.z) / h).abs() < 1e-3);
+    }
+}
@@ -1,5 +1,6 @@
-use crate::point::{barycentric, barycentric_3d, Point, Vec2, Vec3};
+use crate::point::{barycentric, barycentric_f32, Point, Vec2, Vec3};
 use crate::rgb_image::{RGBColor, RGBImage, GREEN_COLOR, RED_COLOR};
+use crate::shader::DepthBuffer;
 use std::cmp::{max, min};
 use std::mem::swap;
 
@@ -108,58 +109,115 @@ impl RGBImage {
         self.line(p2, p3, color);
         self.line(p3, p1, color);
     }
+}
 
-    pub(crate) fn triangle_z_buffer(
-        &mut self,
-        points: [Vec3<u16>; 3],
-        zbuffer: &mut Vec<f32>,
-        color: RGBColor,
-    ) {
-        self.triangle_z_buffer_bary(points, zbuffer, &|_| color);
-    }
-
-    pub(crate) fn triangle_z_buffer_bary(
+impl DepthBuffer {
+    // Screen coordinates are signed with sub-pixel precision, pixels are sampled at their centers.
+    // `plot` gets pixels closer than the buffer with perspective correct barycentric coordinates
+    // and returns whether the depth is written, `inverse_w` is 1/w of every vertex in clip space
+    // (all ones without perspective)
+    pub(crate) fn triangle(
         &mut self,
-        points: [Vec3<u16>; 3],
-        z_buffer: &mut Vec<f32>,
-        color_for_barycentric: &dyn Fn(Vec3<f32>) -> RGBColor,
+        points: [Vec3<f32>; 3],
+        inverse_w: [f32; 3],
+        plot: &mut dyn FnMut(Point, Vec3<f32>) -> bool,
     ) {
-        let mut b_box_min = Vec2 {
-            x: self.width - 1,
-            y: self.height - 1,
+        let bound = |coord: fn(&Vec3<f32>) -> f32, side: u16| {
+            let min = points.iter().map(coord).fold(f32::INFINITY, f32::min);
+            let max = points.iter().map(coord).fold(f32::NEG_INFINITY, f32::max);
+            let last = side as f32 - 1.0;
+            (min.floor().max(0.0), max.ceil().min(last))
         };
-        let mut b_box_max = Vec2 { x: 0, y: 0 };
-        let clamp = b_box_min;
-        for i in 0..3 {
-            b_box_min.x = max(0, min(b_box_min.x, points[i].x));
-            b_box_min.y = max(0, min(b_box_min.y, points[i].y));
-            b_box_max.x = min(clamp.x, max(b_box_max.x, points[i].x));
-            b_box_max.y = min(clamp.y, max(b_box_max.y, points[i].y));
+        let (min_x, max_x) = bound(|p| p.x, self.width);
+        let (min_y, max_y) = bound(|p| p.y, self.height);
+        // also false for NaN coordinates
+        if !(min_x <= max_x && min_y <= max_y) {
+            return;
         }
 
-        for x in b_box_min.x..=b_box_max.x {
-            for y in b_box_min.y..=b_box_max.y {
-                let pts = points.map(|p| p.as_i32());
-                let bc_screen = barycentric_3d(
+        let pts = points.map(|p| Vec2 { x: p.x, y: p.y });
+        for x in min_x as u16..=max_x as u16 {
+            for y in min_y as u16..=max_y as u16 {
+                let bc_screen = barycentric_f32(
                     pts,
                     Vec2 {
-                        x: x as i32,
-                        y: y as i32,
+                        x: x as f32 + 0.5,
+                        y: y as f32 + 0.5,
                     },
                 );
                 if bc_screen.x < 0.0 || bc_screen.y < 0.0 || bc_screen.z < 0.0 {
                     continue;
                 }
+                // depth is already divided by w, so it's linear in screen space
                 let mut z = 0.0;
-                z += pts[0].z as f32 * bc_screen.x;
-                z += pts[1].z as f32 * bc_screen.y;
-                z += pts[2].z as f32 * bc_screen.z;
-                let buffer_index = (x as i32 + y as i32 * self.width as i32) as usize;
-                if z_buffer[buffer_index] < z {
-                    z_buffer[buffer_index] = z;
-                    self.set_pixel(Point { x, y }, color_for_barycentric(bc_screen));
+                z += points[0].z * bc_screen.x;
+                z += points[1].z * bc_screen.y;
+                z += points[2].z * bc_screen.z;
+                let buffer_index = usize::from(x) + usize::from(y) * usize::from(self.width);
+                if self.depth[buffer_index] < z {
+                    let bc = Vec3 {
+                        x: bc_screen.x * inverse_w[0],
+                        y: bc_screen.y * inverse_w[1],
+                        z: bc_screen.z * inverse_w[2],
+                    };
+                    let sum = bc.x + bc.y + bc.z;
+                    let bc = Vec3 {
+                        x: bc.x / sum,
+                        y: bc.y / sum,
+                        z: bc.z / sum,
+                    };
+                    if plot(Point { x, y }, bc) {
+                        self.depth[buffer_index] = z;
+                    }
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_triangle_off_screen_and_sub_pixel_depth() {
+        let mut depth = DepthBuffer::new(4, 4);
+        let mut drawn = [0; 16];
+        let v = |x: f32, y: f32, z: f32| Vec3 { x, y, z };
+        // covers the whole image, the first vertex is far outside of it
+        let back = [v(-10.0, -10.0, 0.5), v(20.0, -1.0, 0.5), v(-1.0, 20.0, 0.5)];
+        depth.triangle(back, [1.0; 3], &mut |p, _| {
+            drawn[usize::from(p.x + p.y * 4)] = 1;
+            true
+        });
+        assert!(drawn.iter().all(|&d| d == 1));
+
+        // less than one depth unit closer
+        let front = [v(0.0, 0.0, 0.75), v(2.0, 0.0, 0.75), v(0.0, 2.0, 0.75)];
+        depth.triangle(front, [1.0; 3], &mut |p, _| {
+            drawn[usize::from(p.x + p.y * 4)] = 2;
+            true
+        });
+        assert_eq!(drawn[0], 2);
+        assert_eq!(depth.depth[0], 0.75);
+        assert_eq!(drawn[3], 1);
+    }
+
+    #[test]
+    fn test_perspective_correct_barycentric() {
+        let mut depth = DepthBuffer::new(8, 1);
+        let v = |x: f32, y: f32| Vec3 { x, y, z: 0.0 };
+        let points = [v(0.0, 0.0), v(8.0, 0.0), v(0.0, 8.0)];
+        // the second vertex is three times further from the camera
+        let mut bcs = vec![];
+        depth.triangle(points, [1.0, 1.0 / 3.0, 1.0], &mut |_, bc| {
+            bcs.push(bc);
+            false
+        });
+        let bc = bcs[3];
+        // screen space coordinates of the pixel center are (0.5, 0.4375, 0.0625)
+        assert!((bc.y - 0.4375 / 3.0 / (0.5 + 0.4375 / 3.0 + 0.0625)).abs() < 1e-6);
+        assert!((bc.x + bc.y + bc.z - 1.0).abs() < 1e-6);
+        assert!(depth.depth.iter().all(|&d| d == -1.0));
+    }
+}
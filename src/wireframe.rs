@@ -1,38 +1,368 @@
-use crate::point::Vec3;
+use crate::material::Material;
+use crate::point::{cross, Vec3};
+use crate::polygon::triangulate;
+use std::fmt::{Display, Formatter};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
-use std::str::FromStr;
+use std::ops::Range;
+use std::path::Path;
+use std::str::{FromStr, SplitAsciiWhitespace};
 
-pub(crate) type Vertex3 = Vec3<f32>;
+pub type Vertex3 = Vec3<f32>;
 
 #[derive(PartialEq, Debug, Copy, Clone)]
-pub(crate) struct Face {
-    pub(crate) vertex_index: usize,
-    pub(crate) texture_index: usize,
-    pub(crate) norm_index: usize,
+pub struct Face {
+    pub vertex_index: usize,
+    pub texture_index: Option<usize>,
+    pub norm_index: Option<usize>,
 }
 
-pub(crate) struct WireframeModel {
-    pub(crate) vertexes: Vec<Vertex3>,
-    pub(crate) faces: Vec<[Face; 3]>,
-    pub(crate) texture_coord: Vec<(f32, f32)>,
-    pub(crate) norm: Vec<Vec3<f32>>,
+/// Tangent of a face corner, `sign` is the bitangent direction as in MikkTSpace.
+#[derive(PartialEq, Debug, Copy, Clone, Default)]
+pub struct Tangent {
+    pub tangent: Vec3<f32>,
+    pub sign: f32,
+}
+
+impl Tangent {
+    pub fn bitangent(&self, normal: Vec3<f32>) -> Vec3<f32> {
+        let b = cross(normal, self.tangent);
+        Vec3 {
+            x: b.x * self.sign,
+            y: b.y * self.sign,
+            z: b.z * self.sign,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct WireframeModel {
+    pub vertexes: Vec<Vertex3>,
+    /// Triangulated `polygons`
+    pub faces: Vec<[Face; 3]>,
+    /// Faces as they are defined in the file, with three or more vertices
+    pub polygons: Vec<Vec<Face>>,
+    pub texture_coord: Vec<(f32, f32)>,
+    pub norm: Vec<Vec3<f32>>,
+    /// Linear `r`, `g`, `b` color for every vertex in `vertexes`, empty if the file has none
+    pub vertex_colors: Vec<Vec3<f32>>,
+    pub materials: Vec<Material>,
+    /// Index in `materials` for every face in `faces`
+    pub face_materials: Vec<Option<usize>>,
+    /// Smoothing group (`s`) for every face in `faces`, 0 is off, empty if the file has none
+    pub face_smoothing_groups: Vec<u32>,
+    /// Tangents for every corner of `faces`, empty until `compute_tangents` is called
+    pub face_tangents: Vec<[Tangent; 3]>,
+    pub groups: Vec<Group>,
+}
+
+/// Named part of the model, `o` or `g` section of an obj file.
+/// Sections with the same name in the same object are merged, so a group may have several ranges.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Group {
+    pub name: String,
+    /// Name of the `o` object the group was started in
+    pub object: Option<String>,
+    /// Ranges in `WireframeModel::faces`
+    pub faces: Vec<Range<usize>>,
+    /// Ranges in `WireframeModel::polygons`
+    pub polygons: Vec<Range<usize>>,
+    /// Hidden groups are skipped by renderers
+    pub visible: bool,
+}
+
+impl Group {
+    pub(crate) fn new(name: String, face: usize, polygon: usize) -> Self {
+        let mut group = Group {
+            name,
+            object: None,
+            faces: vec![],
+            polygons: vec![],
+            visible: true,
+        };
+        group.start(face, polygon);
+        group
+    }
+
+    // Starts a new range of the group
+    pub(crate) fn start(&mut self, face: usize, polygon: usize) {
+        self.faces.push(face..face);
+        self.polygons.push(polygon..polygon);
+    }
+
+    // Extends the last range of the group up to the given face and polygon
+    pub(crate) fn end(&mut self, face: usize, polygon: usize) {
+        if let Some(faces) = self.faces.last_mut() {
+            faces.end = face;
+        }
+        if let Some(polygons) = self.polygons.last_mut() {
+            polygons.end = polygon;
+        }
+    }
+
+    /// Indices of all faces of the group.
+    pub fn face_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.faces.iter().flat_map(|range| range.clone())
+    }
+}
+
+impl WireframeModel {
+    pub fn group(&self, name: &str) -> Option<&Group> {
+        self.groups.iter().find(|g| g.name == name)
+    }
+
+    pub fn group_mut(&mut self, name: &str) -> Option<&mut Group> {
+        self.groups.iter_mut().find(|g| g.name == name)
+    }
+
+    /// Moves vertexes of the named group, vertexes shared with other groups are moved as well.
+    /// Returns `false` if there is no such group.
+    pub fn transform_group(&mut self, name: &str, transform: impl Fn(Vertex3) -> Vertex3) -> bool {
+        let faces: Vec<usize> = match self.group(name) {
+            Some(group) => group.face_indices().collect(),
+            None => return false,
+        };
+        let mut moved = vec![false; self.vertexes.len()];
+        for face in faces {
+            for f in self.faces[face] {
+                if !moved[f.vertex_index] {
+                    moved[f.vertex_index] = true;
+                    self.vertexes[f.vertex_index] = transform(self.vertexes[f.vertex_index]);
+                }
+            }
+        }
+        true
+    }
+
+    /// Indices of `faces` which don't belong to hidden groups.
+    pub fn visible_faces(&self) -> Vec<usize> {
+        Self::visible(
+            self.faces.len(),
+            self.hidden_groups().flat_map(|g| g.faces.iter().cloned()),
+        )
+    }
+
+    /// Indices of `polygons` which don't belong to hidden groups.
+    pub fn visible_polygons(&self) -> Vec<usize> {
+        Self::visible(
+            self.polygons.len(),
+            self.hidden_groups()
+                .flat_map(|g| g.polygons.iter().cloned()),
+        )
+    }
+
+    fn hidden_groups(&self) -> impl Iterator<Item = &Group> {
+        self.groups.iter().filter(|g| !g.visible)
+    }
+
+    fn visible(count: usize, hidden: impl Iterator<Item = Range<usize>>) -> Vec<usize> {
+        let mut visible = vec![true; count];
+        for range in hidden {
+            visible[range].fill(false);
+        }
+        (0..count).filter(|&i| visible[i]).collect()
+    }
+
+    /// Index in `groups` for every face in `faces`
+    pub fn face_groups(&self) -> Vec<Option<usize>> {
+        let mut face_groups = vec![None; self.faces.len()];
+        for (i, group) in self.groups.iter().enumerate() {
+            for face in group.face_indices() {
+                face_groups[face] = Some(i);
+            }
+        }
+        face_groups
+    }
+}
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io {
+        path: String,
+        error: std::io::Error,
+    },
+    Image {
+        path: String,
+        error: image::ImageError,
+    },
+    Parse {
+        path: String,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl Display for ObjError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ObjError::Io { path, error } => write!(f, "{}: {}", path, error),
+            ObjError::Image { path, error } => write!(f, "{}: {}", path, error),
+            ObjError::Parse {
+                path,
+                line,
+                column,
+                message,
+            } => write!(f, "{}:{}:{}: {}", path, line, column, message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ObjError::Io { error, .. } => Some(error),
+            ObjError::Image { error, .. } => Some(error),
+            ObjError::Parse { .. } => None,
+        }
+    }
+}
+
+/// Malformed field of a single line, `column` is a byte offset in the parsed string.
+#[derive(PartialEq, Debug)]
+pub struct FieldError {
+    pub column: usize,
+    pub message: String,
+}
+
+impl FieldError {
+    pub(crate) fn new(column: usize, message: String) -> Self {
+        FieldError { column, message }
+    }
+}
+
+// Byte offset of the `token` slice inside the `s` string
+pub(crate) fn offset(s: &str, token: &str) -> usize {
+    token.as_ptr() as usize - s.as_ptr() as usize
+}
+
+// Path of the `file` referenced from the file at `path`
+pub(crate) fn relative_path(path: &str, file: &str) -> String {
+    let directory = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+    directory.join(file).to_string_lossy().to_string()
+}
+
+// Path in the temporary directory of this test process, tests run in parallel
+// and other checkouts may run them at the same time, so `name` should be unique per test
+#[cfg(test)]
+pub(crate) fn test_path(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir()
+        .join(format!("tiny_renderer_{}", std::process::id()))
+        .join(name);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    path
+}
+
+// Writes the test file and returns its path
+#[cfg(test)]
+pub(crate) fn write_test_file(name: &str, contents: impl AsRef<[u8]>) -> String {
+    let path = test_path(name);
+    std::fs::write(&path, contents).unwrap();
+    path.to_string_lossy().to_string()
+}
+
+/// Line of an OBJ or MTL file split into the keyword and the rest fields.
+pub(crate) struct ObjLine<'a> {
+    path: &'a str,
+    number: usize,
+    text: &'a str,
+    pub(crate) keyword: &'a str,
+    pub(crate) fields: &'a str,
+}
+
+impl ObjLine<'_> {
+    pub(crate) fn error(&self, e: FieldError) -> ObjError {
+        self.error_at_column(offset(self.text, self.fields) + e.column, e.message)
+    }
+
+    pub(crate) fn error_at(&self, token: &str, message: String) -> ObjError {
+        self.error_at_column(offset(self.text, token), message)
+    }
+
+    fn error_at_column(&self, column: usize, message: String) -> ObjError {
+        ObjError::Parse {
+            path: self.path.to_string(),
+            line: self.number,
+            column: column + 1,
+            message,
+        }
+    }
+
+    pub(crate) fn unsupported(&self) {
+        println!("unsupported line {}", self.text.trim())
+    }
+
+    // Problems which don't prevent loading, like missing materials, are only reported
+    pub(crate) fn warning(&self, error: ObjError) {
+        println!("warning: {}", error)
+    }
+}
+
+// Calls `parse_line` for every line skipping empty ones and comments
+pub(crate) fn read_lines(
+    path: &str,
+    mut parse_line: impl FnMut(&ObjLine) -> Result<(), ObjError>,
+) -> Result<(), ObjError> {
+    let io_error = |error| ObjError::Io {
+        path: path.to_string(),
+        error,
+    };
+    let file = File::open(path).map_err(io_error)?;
+    let reader = BufReader::new(file);
+
+    for (i, text) in reader.lines().enumerate() {
+        let text = text.map_err(io_error)?;
+        let s = text.trim();
+        if s.is_empty() || s.starts_with('#') {
+            continue;
+        }
+        let (keyword, fields) = match s.split_once(char::is_whitespace) {
+            Some((keyword, fields)) => (keyword, fields),
+            None => (s, &s[s.len()..]),
+        };
+        parse_line(&ObjLine {
+            path,
+            number: i + 1,
+            text: &text,
+            keyword,
+            fields,
+        })?;
+    }
+    Ok(())
+}
+
+pub(crate) fn float_field(
+    s: &str,
+    it: &mut SplitAsciiWhitespace,
+    name: &str,
+) -> Result<f32, FieldError> {
+    let token = it
+        .next()
+        .ok_or_else(|| FieldError::new(s.len(), format!("expected {}", name)))?;
+    token.parse::<f32>().map_err(|_| {
+        FieldError::new(
+            offset(s, token),
+            format!("expected float {}, found `{}`", name, token),
+        )
+    })
+}
+
+// Number of elements read so far, face indices can't refer to anything else
+#[derive(Clone, Copy)]
+struct ElementCounts {
+    vertexes: usize,
+    texture_coords: usize,
+    norms: usize,
 }
 
 impl FromStr for Vertex3 {
-    type Err = std::num::ParseFloatError;
+    type Err = FieldError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut it = s.split_ascii_whitespace();
-        let mut parse_float = || {
-            it.next()
-                .expect("point value")
-                .parse::<f32>()
-                .expect("float value")
-        };
-        let x = parse_float();
-        let y = parse_float();
-        let z = parse_float();
+        let x = float_field(s, &mut it, "x value")?;
+        let y = float_field(s, &mut it, "y value")?;
+        let z = float_field(s, &mut it, "z value")?;
         Ok(Vertex3 { x, y, z })
     }
 }
@@ -53,38 +383,114 @@ mod tests {
     #[test]
     fn test_face_from_str() {
         let s = "f 1193/1240/1193 1180/1227/1180 1179/1226/1178";
-        let face = WireframeModel::face_from_str(&s[2..]);
+        let counts = ElementCounts {
+            vertexes: 1193,
+            texture_coords: 1240,
+            norms: 1193,
+        };
+        let face = WireframeModel::face_from_str(&s[2..], counts).unwrap();
         let expected: [Face; 3] = [
             Face {
                 vertex_index: 1192,
-                texture_index: 1239,
-                norm_index: 1192,
+                texture_index: Some(1239),
+                norm_index: Some(1192),
             },
             Face {
                 vertex_index: 1179,
-                texture_index: 1226,
-                norm_index: 1179,
+                texture_index: Some(1226),
+                norm_index: Some(1179),
             },
             Face {
                 vertex_index: 1178,
-                texture_index: 1225,
-                norm_index: 1177,
+                texture_index: Some(1225),
+                norm_index: Some(1177),
             },
         ];
         assert_eq!(face, expected);
     }
 
+    #[test]
+    fn test_polygon_face_from_str() {
+        let s = "f 1/1/1 2/2/1 3/3/1 4/4/1 5/5/1";
+        let counts = ElementCounts {
+            vertexes: 5,
+            texture_coords: 5,
+            norms: 1,
+        };
+        let polygon = WireframeModel::face_from_str(&s[2..], counts).unwrap();
+        assert_eq!(polygon.len(), 5);
+        assert_eq!(polygon[4].vertex_index, 4);
+
+        let error = WireframeModel::face_from_str("1/1/1 2/2/1", counts).unwrap_err();
+        assert_eq!(error.column, 11);
+        assert_eq!(error.message, "expected face vertex");
+    }
+
+    #[test]
+    fn test_face_index_forms() {
+        let counts = ElementCounts {
+            vertexes: 4,
+            texture_coords: 4,
+            norms: 4,
+        };
+        let face = |vertex_index, texture_index, norm_index| Face {
+            vertex_index,
+            texture_index,
+            norm_index,
+        };
+        let parse = |s: &str| WireframeModel::face_from_str(s, counts).unwrap();
+        assert_eq!(
+            parse("1 2 3"),
+            [
+                face(0, None, None),
+                face(1, None, None),
+                face(2, None, None)
+            ]
+        );
+        assert_eq!(
+            parse("1/2 2/3 3/4"),
+            [
+                face(0, Some(1), None),
+                face(1, Some(2), None),
+                face(2, Some(3), None)
+            ]
+        );
+        assert_eq!(
+            parse("1//4 2//3 3//2"),
+            [
+                face(0, None, Some(3)),
+                face(1, None, Some(2)),
+                face(2, None, Some(1))
+            ]
+        );
+        assert_eq!(
+            parse("-3/-1/-4 -2/-1/-4 -1/-1/-4"),
+            [
+                face(1, Some(3), Some(0)),
+                face(2, Some(3), Some(0)),
+                face(3, Some(3), Some(0))
+            ]
+        );
+
+        let error = WireframeModel::face_from_str("1 2 -5", counts).unwrap_err();
+        assert_eq!(error.column, 4);
+        assert_eq!(error.message, "face index -5 out of range, only 4 vertices");
+        let error = WireframeModel::face_from_str("1 2 3/1/1/1", counts).unwrap_err();
+        assert_eq!(error.column, 10);
+        assert_eq!(error.message, "unexpected index `1`");
+    }
+
     #[test]
     fn test_texture_coord_from_str() {
         let s = "vt  0.532 0.923 0.000";
-        let face = WireframeModel::texture_coord_from_str(&s[2..]);
+        let face = WireframeModel::texture_coord_from_str(&s[2..]).unwrap();
         assert_eq!(face, (0.532, 0.923));
     }
 
     #[test]
     fn test_texture_norm_from_str() {
         let s = "vn  -0.319 -0.065 0.946";
-        let norm = WireframeModel::norm_from_str(&s[2..]);
+        let norm = WireframeModel::norm_from_str(&s[2..]).unwrap();
         assert_eq!(
             norm,
             Vec3 {
@@ -94,100 +500,408 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_face_index_out_of_range() {
+        let s = "f 1193/1240/1193 1180/1227/1180 1179/1226/1178";
+        let counts = ElementCounts {
+            vertexes: 1180,
+            texture_coords: 1240,
+            norms: 1193,
+        };
+        let error = WireframeModel::face_from_str(&s[2..], counts).unwrap_err();
+        assert_eq!(
+            error,
+            FieldError {
+                column: 0,
+                message: "face index 1193 out of range, only 1180 vertices".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_missing_field() {
+        let s = "vn  -0.319 -0.065";
+        let error = WireframeModel::norm_from_str(&s[2..]).unwrap_err();
+        assert_eq!(error.column, 15);
+        assert_eq!(error.message, "expected z value");
+
+        let s = "v 0.123 O.234 0.345";
+        let error = Vertex3::from_str(&s[2..]).unwrap_err();
+        assert_eq!(error.column, 6);
+        assert_eq!(error.message, "expected float y value, found `O.234`");
+    }
+
+    #[test]
+    fn test_groups() {
+        let path = write_test_file(
+            "groups.obj",
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3\n\
+             o Cube\ng Cube_Top\ns 1\nf 1 2 3 4\nf 2 3 4\ng Cube_Bottom\ns off\nf 4 3 1\n",
+        );
+        let mut model = WireframeModel::from_file(path).unwrap();
+        let names: Vec<&str> = model.groups.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, ["default", "Cube_Top", "Cube_Bottom"]);
+        let top = model.group("Cube_Top").unwrap();
+        assert_eq!(top.face_indices().collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!(top.polygons.len(), 1);
+        assert_eq!(top.polygons[0], 1..3);
+        assert_eq!(top.object.as_deref(), Some("Cube"));
+        assert_eq!(
+            model.face_groups(),
+            [Some(0), Some(1), Some(1), Some(1), Some(2)]
+        );
+        assert_eq!(model.face_smoothing_groups, [0, 1, 1, 1, 0]);
+
+        model.group_mut("Cube_Top").unwrap().visible = false;
+        assert_eq!(model.visible_faces(), [0, 4]);
+        assert_eq!(model.visible_polygons(), [0, 3]);
+
+        assert!(model.transform_group("Cube_Bottom", |v| Vec3 { z: v.z + 1.0, ..v }));
+        let z: Vec<f32> = model.vertexes.iter().map(|v| v.z).collect();
+        assert_eq!(z, [1.0, 0.0, 1.0, 1.0]);
+        assert!(!model.transform_group("Sphere", |v| v));
+    }
+
+    #[test]
+    fn test_repeated_groups() {
+        let path = write_test_file(
+            "repeated_groups.obj",
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+             g a\nf 1 2 3\ng b\nf 1 3 4\nf 2 3 4\ng a\nf 4 3 1\n",
+        );
+        let mut model = WireframeModel::from_file(path).unwrap();
+        let names: Vec<&str> = model.groups.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, ["a", "b"]);
+        let a = model.group("a").unwrap();
+        assert_eq!(a.faces, [0..1, 3..4]);
+        assert_eq!(a.polygons, [0..1, 3..4]);
+        assert_eq!(a.object, None);
+        assert_eq!(model.face_groups(), [Some(0), Some(1), Some(1), Some(0)]);
+
+        model.group_mut("a").unwrap().visible = false;
+        assert_eq!(model.visible_faces(), [1, 2]);
+        assert!(model.transform_group("a", |v| Vec3 { z: v.z + 1.0, ..v }));
+        let z: Vec<f32> = model.vertexes.iter().map(|v| v.z).collect();
+        assert_eq!(z, [1.0, 1.0, 1.0, 1.0]);
+
+        // groups with the same name in different objects stay apart
+        let path = write_test_file(
+            "object_groups.obj",
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\n\
+             o A\ng body\nf 1 2 3\no B\ng body\nf 3 2 1\no A\ng body\nf 2 3 1\n",
+        );
+        let model = WireframeModel::from_file(path).unwrap();
+        let groups: Vec<(Option<&str>, &str, usize)> = model
+            .groups
+            .iter()
+            .map(|g| (g.object.as_deref(), g.name.as_str(), g.faces.len()))
+            .collect();
+        assert_eq!(groups, [(Some("A"), "body", 2), (Some("B"), "body", 1)]);
+        assert_eq!(model.face_groups(), [Some(0), Some(1), Some(0)]);
+
+        let copy_path = test_path("object_groups_copy.obj")
+            .to_string_lossy()
+            .to_string();
+        model.write_obj(copy_path.clone()).unwrap();
+        let copy = WireframeModel::from_file(copy_path).unwrap();
+        assert_eq!(copy.groups, model.groups);
+    }
+
+    #[test]
+    fn test_from_file_reports_line_and_column() {
+        let path = write_test_file(
+            "broken.obj",
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvn 0 0 1\n# comment\nf 1/1/1 2/1/1 4/1/1\n",
+        );
+        let error = WireframeModel::from_file(path.clone()).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            format!("{}:7:15: face index 4 out of range, only 3 vertices", path)
+        );
+    }
+
+    #[test]
+    fn test_write_obj() {
+        let path = write_test_file(
+            "write.obj",
+            "v 0.5 0 -1e-3\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0.25 0.75\nvn 0 0 1\n\
+             f 1 2 3\no Mesh\ng Quad\nf 1/1 2/1 3/1 4/1\nf 1//1 3//1 4//1\n\
+             g Tri\nf 4/1/1 3/1/1 2/1/1\n\
+             g Quad\nf 1 2 3\n",
+        );
+        let model = WireframeModel::from_file(path).unwrap();
+        let copy_path = test_path("write_copy.obj").to_string_lossy().to_string();
+        model.write_obj(copy_path.clone()).unwrap();
+
+        let copy = WireframeModel::from_file(copy_path).unwrap();
+        assert_eq!(copy.vertexes, model.vertexes);
+        assert_eq!(copy.texture_coord, model.texture_coord);
+        assert_eq!(copy.norm, model.norm);
+        assert_eq!(copy.polygons, model.polygons);
+        assert_eq!(copy.faces, model.faces);
+        assert_eq!(copy.groups, model.groups);
+    }
 }
 
 impl WireframeModel {
-    fn texture_coord_from_str(s: &str) -> (f32, f32) {
+    fn texture_coord_from_str(s: &str) -> Result<(f32, f32), FieldError> {
         let mut it = s.split_ascii_whitespace();
-        let x = it
-            .next()
-            .expect("x value")
-            .parse::<f32>()
-            .expect("float value");
-        let y = it
-            .next()
-            .expect("y value")
-            .parse::<f32>()
-            .expect("float value");
-        return (x, y);
-    }
-
-    fn face_from_str(s: &str) -> [Face; 3] {
-        fn index(s: &str) -> usize {
-            let i = s.parse::<i32>().expect("usize value") - 1; // in wavefront obj all indices start at 1, not zero
-            return i as usize;
-        }
-
-        fn face(s: &str) -> Face {
-            let mut it = s.split("/");
-            return Face {
-                vertex_index: index(it.next().expect("uv index value")),
-                texture_index: index(it.next().expect("vertex index value")),
-                norm_index: index(it.next().expect("normal index value")),
-            };
+        let x = float_field(s, &mut it, "x value")?;
+        let y = float_field(s, &mut it, "y value")?;
+        Ok((x, y))
+    }
+
+    fn face_from_str(s: &str, counts: ElementCounts) -> Result<Vec<Face>, FieldError> {
+        fn index(
+            s: &str,
+            token: &str,
+            name: &str,
+            elements: &str,
+            count: usize,
+        ) -> Result<usize, FieldError> {
+            let column = offset(s, token);
+            let i = token.parse::<i64>().map_err(|_| {
+                FieldError::new(
+                    column,
+                    format!("expected {} index, found `{}`", name, token),
+                )
+            })?;
+            // in wavefront obj all indices start at 1, not zero,
+            // negative indices are relative to the end of already defined elements
+            let resolved = if i > 0 { i - 1 } else { count as i64 + i };
+            if i == 0 || resolved < 0 || resolved >= count as i64 {
+                return Err(FieldError::new(
+                    column,
+                    format!(
+                        "{} index {} out of range, only {} {}",
+                        name, i, count, elements
+                    ),
+                ));
+            }
+            Ok(resolved as usize)
         }
-        let mut it = s.split_ascii_whitespace();
-        let mut parse_face = || face(it.next().expect("coordinates"));
-        return [parse_face(), parse_face(), parse_face()];
+
+        // v, v/vt, v//vn or v/vt/vn
+        let face = |token: &str| -> Result<Face, FieldError> {
+            let mut it = token.split('/');
+            let v = it.next().unwrap_or_default();
+            let vt = it.next().filter(|vt| !vt.is_empty());
+            let vn = it.next().filter(|vn| !vn.is_empty());
+            if let Some(extra) = it.next() {
+                return Err(FieldError::new(
+                    offset(s, extra),
+                    format!("unexpected index `{}`", extra),
+                ));
+            }
+            Ok(Face {
+                vertex_index: index(s, v, "face", "vertices", counts.vertexes)?,
+                texture_index: vt
+                    .map(|vt| {
+                        index(
+                            s,
+                            vt,
+                            "texture",
+                            "texture coordinates",
+                            counts.texture_coords,
+                        )
+                    })
+                    .transpose()?,
+                norm_index: vn
+                    .map(|vn| index(s, vn, "normal", "normals", counts.norms))
+                    .transpose()?,
+            })
+        };
+        let polygon = s
+            .split_ascii_whitespace()
+            .map(face)
+            .collect::<Result<Vec<Face>, FieldError>>()?;
+        if polygon.len() < 3 {
+            return Err(FieldError::new(s.len(), "expected face vertex".to_string()));
+        }
+        Ok(polygon)
     }
 
-    fn norm_from_str(s: &str) -> Vec3<f32> {
+    fn norm_from_str(s: &str) -> Result<Vec3<f32>, FieldError> {
         let mut it = s.split_ascii_whitespace();
-        let x = it
-            .next()
-            .expect("x value")
-            .parse::<f32>()
-            .expect("float value");
-        let y = it
-            .next()
-            .expect("y value")
-            .parse::<f32>()
-            .expect("float value");
-        let z = it
-            .next()
-            .expect("z value")
-            .parse::<f32>()
-            .expect("float value");
-        Vec3 { x, y, z }
-    }
-
-    pub fn from_file(path: String) -> WireframeModel {
-        let file = File::open(path).unwrap();
-        let reader = BufReader::new(file);
+        let x = float_field(s, &mut it, "x value")?;
+        let y = float_field(s, &mut it, "y value")?;
+        let z = float_field(s, &mut it, "z value")?;
+        Ok(Vec3 { x, y, z })
+    }
 
+    /// Loads wavefront obj file, materials from `mtllib` are loaded relative to it.
+    pub fn from_obj(path: String) -> Result<WireframeModel, ObjError> {
         let mut vertexes: Vec<Vertex3> = vec![];
         let mut faces: Vec<[Face; 3]> = vec![];
+        let mut polygons: Vec<Vec<Face>> = vec![];
         let mut texture_coord: Vec<(f32, f32)> = vec![];
         let mut norm: Vec<Vec3<f32>> = vec![];
+        let mut materials: Vec<Material> = vec![];
+        let mut face_materials: Vec<Option<usize>> = vec![];
+        let mut face_smoothing_groups: Vec<u32> = vec![];
+        let mut smoothing_group: Option<u32> = None;
+        let mut material: Option<usize> = None;
+        let mut groups: Vec<Group> = vec![];
+        let mut group: Option<usize> = None;
+        let mut object: Option<String> = None;
 
-        for (_, line) in reader.lines().enumerate() {
-            let line = line.unwrap();
-            let s = line.trim();
-            if s.is_empty() || s.starts_with("#") {
-                continue;
-            }
-            if s.starts_with("v ") {
-                let v = Vertex3::from_str(&s[2..]).unwrap();
-                vertexes.push(v);
-            } else if s.starts_with("f ") {
-                let f = Self::face_from_str(&s[2..]);
-                faces.push(f);
-            } else if s.starts_with("vt") {
-                let c = Self::texture_coord_from_str(&s[2..]);
-                texture_coord.push(c);
-            } else if s.starts_with("vn") {
-                let v = Self::norm_from_str(&s[2..]);
-                norm.push(v);
-            } else {
-                println!("unsupported line {}", s)
+        read_lines(&path, |line| {
+            let parse_error = |e| line.error(e);
+            match line.keyword {
+                "v" => {
+                    let v = Vertex3::from_str(line.fields).map_err(parse_error)?;
+                    vertexes.push(v);
+                }
+                "f" => {
+                    let counts = ElementCounts {
+                        vertexes: vertexes.len(),
+                        texture_coords: texture_coord.len(),
+                        norms: norm.len(),
+                    };
+                    let polygon = Self::face_from_str(line.fields, counts).map_err(parse_error)?;
+                    let points: Vec<Vertex3> =
+                        polygon.iter().map(|f| vertexes[f.vertex_index]).collect();
+                    for t in triangulate(&points) {
+                        faces.push(t.map(|i| polygon[i]));
+                        face_materials.push(material);
+                        face_smoothing_groups.push(smoothing_group.unwrap_or(0));
+                    }
+                    polygons.push(polygon);
+                    let current = *group.get_or_insert_with(|| {
+                        groups.push(Group::new("default".to_string(), 0, 0));
+                        groups.len() - 1
+                    });
+                    groups[current].end(faces.len(), polygons.len());
+                }
+                "o" | "g" => {
+                    let name = line.fields.trim().to_string();
+                    if line.keyword == "o" {
+                        object = Some(name.clone());
+                    }
+                    // a group repeated later in the same object continues with a new range
+                    let index = groups
+                        .iter()
+                        .position(|g| g.name == name && g.object == object);
+                    group = match index {
+                        Some(i) => {
+                            groups[i].start(faces.len(), polygons.len());
+                            Some(i)
+                        }
+                        None => {
+                            groups.push(Group {
+                                object: object.clone(),
+                                ..Group::new(name, faces.len(), polygons.len())
+                            });
+                            Some(groups.len() - 1)
+                        }
+                    };
+                }
+                "vt" => {
+                    let c = Self::texture_coord_from_str(line.fields).map_err(parse_error)?;
+                    texture_coord.push(c);
+                }
+                "vn" => {
+                    let v = Self::norm_from_str(line.fields).map_err(parse_error)?;
+                    norm.push(v);
+                }
+                "mtllib" => {
+                    for file in line.fields.split_ascii_whitespace() {
+                        match Material::from_file(relative_path(&path, file)) {
+                            Ok(loaded) => materials.extend(loaded),
+                            // faces are drawn with the default material
+                            Err(error @ ObjError::Io { .. }) => line.warning(error),
+                            Err(error) => return Err(error),
+                        }
+                    }
+                }
+                "usemtl" => {
+                    let name = line.fields.trim();
+                    material = materials.iter().position(|m| m.name == name);
+                    if material.is_none() {
+                        line.warning(line.error_at(name, format!("unknown material `{}`", name)));
+                    }
+                }
+                "s" => {
+                    let token = line.fields.trim();
+                    let group = match token {
+                        "off" => Ok(0),
+                        _ => token.parse::<u32>(),
+                    };
+                    smoothing_group = Some(group.map_err(|_| {
+                        line.error_at(
+                            token,
+                            format!("expected smoothing group, found `{}`", token),
+                        )
+                    })?);
+                }
+                _ => line.unsupported(),
             }
+            Ok(())
+        })?;
+        for group in &mut groups {
+            group.faces.retain(|range| !range.is_empty());
+            group.polygons.retain(|range| !range.is_empty());
+        }
+        groups.retain(|g| !g.faces.is_empty());
+        if smoothing_group.is_none() {
+            face_smoothing_groups.clear();
         }
 
-        WireframeModel {
+        Ok(WireframeModel {
             vertexes,
             faces,
+            polygons,
             texture_coord,
             norm,
+            vertex_colors: vec![],
+            materials,
+            face_materials,
+            face_smoothing_groups,
+            face_tangents: vec![],
+            groups,
+        })
+    }
+
+    /// Writes vertices, texture coordinates, normals, groups and polygons as a wavefront obj file.
+    /// Materials are not saved.
+    pub fn write_obj(&self, path: String) -> Result<(), ObjError> {
+        let mut obj = String::new();
+        for v in &self.vertexes {
+            obj += &format!("v {} {} {}\n", v.x, v.y, v.z);
+        }
+        for (u, v) in &self.texture_coord {
+            obj += &format!("vt {} {}\n", u, v);
+        }
+        for n in &self.norm {
+            obj += &format!("vn {} {} {}\n", n.x, n.y, n.z);
+        }
+        let mut object = None;
+        for (i, polygon) in self.polygons.iter().enumerate() {
+            if let Some(group) = self.groups.iter().find(|g| {
+                g.polygons
+                    .iter()
+                    .any(|range| range.start == i && !range.is_empty())
+            }) {
+                if let Some(name) = group.object.as_ref().filter(|_| group.object != object) {
+                    obj += &format!("o {}\n", name);
+                    object = group.object.clone();
+                }
+                obj += &format!("g {}\n", group.name);
+            }
+            obj += "f";
+            // obj indices start at 1
+            for face in polygon {
+                obj += &format!(" {}", face.vertex_index + 1);
+                match (face.texture_index, face.norm_index) {
+                    (None, None) => {}
+                    (Some(vt), None) => obj += &format!("/{}", vt + 1),
+                    (None, Some(vn)) => obj += &format!("//{}", vn + 1),
+                    (Some(vt), Some(vn)) => obj += &format!("/{}/{}", vt + 1, vn + 1),
+                }
+            }
+            obj += "\n";
         }
+        std::fs::write(&path, obj).map_err(|error| ObjError::Io { path, error })
     }
 }